@@ -1,22 +1,76 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::escrow::{Erc20, Escrow, EscrowError, EscrowRef};
+
+#[cfg(test)]
+#[path = "../mock_erc20.rs"]
+mod mock_erc20;
+
 #[ink::contract]
 mod escrow {
 
-    use blake2_rfc::blake2b::Blake2b;
     use ink::env::{
         call::{build_call, ExecutionInput, Selector},
+        hash::Blake2x256,
         DefaultEnvironment,
     };
+    use ink::prelude::string::String;
     use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
 
+    const TRANSFER_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::transfer");
+    const TRANSFER_FROM_SELECTOR: [u8; 4] = [0, 0, 0, 6];
+    const ALLOWANCE_SELECTOR: [u8; 4] = [0, 0, 0, 7];
+    const GET_NAME_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::get_name");
+    const GET_SYMBOL_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::get_symbol");
+    const VERSION: u32 = 1;
+    const MAX_BPS: u16 = 10_000;
+    // failed transfers after which a token is paused automatically
+    const AUTO_PAUSE_THRESHOLD: u32 = 3;
+
+    // `a * b / denom` rounded down, `None` on overflow or a zero `denom`
+    fn mul_div(a: Balance, b: Balance, denom: Balance) -> Option<Balance> {
+        a.checked_mul(b)?.checked_div(denom)
+    }
+
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum EscrowError {
         InsufficientBalance,
         UnsupportedToken,
         TransferFailed,
+        ActionNotQueued,
+        TimelockNotElapsed,
+        LengthMismatch,
+        InvalidReceipt,
+        NotDepositor,
+        InvalidFee,
+        RateLimited,
+        TokenPaused,
+        DepositCapExceeded,
+        PoolNotSet,
+        InsufficientShares,
+        Overflow,
+        NotAuthorized,
+        SharesOutstanding,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum AdminAction {
+        Withdraw { token: AccountId, amount: Balance },
+        WithdrawSplit {
+            token: AccountId,
+            recipients: Vec<AccountId>,
+            amounts: Vec<Balance>,
+        },
+        WithdrawAll,
+        WithdrawRange { start: u32, limit: u32 },
+        SetAdmin { new_admin: AccountId },
+        SetWithdrawLimit {
+            max_per_period: Balance,
+            period: Timestamp,
+        },
     }
 
     #[ink(event)]
@@ -30,6 +84,26 @@ mod escrow {
     #[ink(event)]
     pub struct WithdrawAll {}
 
+    #[ink(event)]
+    pub struct WithdrawRange {
+        start: u32,
+        limit: u32,
+    }
+
+    /// A token whose transfer reverted during `withdraw_all`; its balance stays in the escrow.
+    #[ink(event)]
+    pub struct WithdrawSkipped {
+        #[ink(topic)]
+        token: AccountId,
+    }
+
+    /// A token paused after too many of its transfers failed.
+    #[ink(event)]
+    pub struct AutoPaused {
+        #[ink(topic)]
+        token: AccountId,
+    }
+
 
     #[ink(event)]
     pub struct Withdrawal {
@@ -51,6 +125,12 @@ mod escrow {
         fn get_owner(&self) -> AccountId;
     }
 
+    // (token, depositor, amount) of a single deposit
+    pub type DepositReceipt = (AccountId, AccountId, Balance);
+
+    // (name, symbol) reported by a token
+    pub type TokenMetadata = (String, String);
+
     #[ink(storage)]
     pub struct Escrow {
         // list of supported tokens
@@ -59,24 +139,287 @@ mod escrow {
         balances: Mapping<AccountId, Balance>,
         // admin of the escrow
         admin: AccountId,
+        // delay before a queued admin action can be executed
+        delay: Timestamp,
+        // mapping from queued admin action to its earliest execution time
+        queued_actions: Mapping<Hash, Timestamp>,
+        // mapping from receipt id to its deposit
+        deposit_receipts: Mapping<u64, DepositReceipt>,
+        // id handed out to the next deposit
+        next_receipt_id: u64,
+        // fee retained on admin withdrawals, in basis points
+        withdraw_fee_bps: u16,
+        // mapping from token to withdraw fees retained and not yet claimed
+        withdraw_fees: Mapping<AccountId, Balance>,
+        // mapping from user to the number of deposits they made
+        deposit_count: Mapping<AccountId, u32>,
+        // mapping from user to the number of withdrawals they made
+        withdraw_count: Mapping<AccountId, u32>,
+        // cap on the amount withdrawn per period, zero disables it
+        max_withdraw_per_period: Balance,
+        // length of a withdraw window
+        period: Timestamp,
+        // start of the current withdraw window
+        period_start: Timestamp,
+        // amount withdrawn in the current window
+        withdrawn_in_period: Balance,
+        // tokens whose deposits and withdrawals are temporarily blocked
+        token_paused: Mapping<AccountId, bool>,
+        // mapping from (token, depositor) to the amount they deposited and still own
+        deposited: Mapping<(AccountId, AccountId), Balance>,
+        // contract allowed to spend deposits on the depositors' behalf
+        operator: Option<AccountId>,
+        // mapping from token to the most the escrow may hold of it, unset means no cap
+        deposit_caps: Mapping<AccountId, Balance>,
+        // mapping from token to its cached metadata, for display
+        token_metadata: Mapping<AccountId, TokenMetadata>,
+        // mapping from token to the number of its transfers that failed since it was last unpaused
+        failed_transfers: Mapping<AccountId, u32>,
+        // token pooled by `deposit_for_shares`, kept apart from the admin's balances
+        pool_token: Option<AccountId>,
+        // amount of the pool token backing the pool shares
+        pool_balance: Balance,
+        pool_shares: Mapping<AccountId, Balance>,
+        total_pool_shares: Balance,
+        // only allowlisted accounts may deposit while set
+        permissioned: bool,
+        depositor_allowlist: Mapping<AccountId, bool>,
+        // informational events are skipped when unset
+        emit_events: bool,
     }
 
     impl Escrow {
-        fn calculate_selector(function_name: &str) -> [u8; 4] {
-            let mut hasher = Blake2b::new(32);
-            hasher.update(function_name.as_bytes());
-            let result = hasher.finalize();
-            let mut selector = [0u8; 4];
-            selector.copy_from_slice(&result.as_bytes()[0..4]);
-            selector
+        // emits through `EmitEvent::<Escrow>` explicitly: when another contract crate with
+        // events is linked in (as the e2e tests do), `self.env().emit_event` is ambiguous
+        fn emit_event<E>(&self, event: E)
+        where
+            E: Into<<Escrow as ink::reflect::ContractEventBase>::Type>,
+        {
+            ink::codegen::EmitEvent::<Escrow>::emit_event(self.env(), event);
+        }
+
+        // only the sweep markers can be switched off, the token transfers
+        // already show what a sweep paid out
+        fn emit_info_event<E>(&self, event: E)
+        where
+            E: Into<<Escrow as ink::reflect::ContractEventBase>::Type>,
+        {
+            if self.emit_events {
+                self.emit_event(event);
+            }
+        }
+
+        fn invoke_token<Args, R>(
+            &self,
+            token: AccountId,
+            input: ExecutionInput<Args>,
+        ) -> Result<R, EscrowError>
+        where
+            Args: scale::Encode,
+            R: scale::Decode,
+        {
+            let call = build_call::<DefaultEnvironment>()
+                .call(token)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(input)
+                .returns::<R>()
+                .params();
+
+            // a reverted token call is reported instead of being silently ignored
+            #[cfg(not(test))]
+            let result = call.try_invoke().ok().and_then(|result| result.ok());
+            #[cfg(test)]
+            let result = crate::mock_erc20::try_invoke(self.env().account_id(), &call);
+            result.ok_or(EscrowError::TransferFailed)
+        }
+
+        fn transfer_token(
+            &mut self,
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), EscrowError> {
+            let transfer_selector = Selector::new(TRANSFER_SELECTOR);
+
+            let result = self
+                .invoke_token::<_, Result<Balance, EscrowError>>(
+                    token,
+                    ExecutionInput::new(transfer_selector)
+                        .push_arg(to)
+                        .push_arg(amount),
+                )
+                .and_then(|result| result.map_err(|_| EscrowError::TransferFailed));
+            result.map(|_| ())
+        }
+
+        fn transfer_token_from(
+            &mut self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), EscrowError> {
+            let transfer_from_selector = Selector::new(TRANSFER_FROM_SELECTOR);
+
+            let result = self
+                .invoke_token::<_, Result<Balance, EscrowError>>(
+                    token,
+                    ExecutionInput::new(transfer_from_selector)
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount),
+                )
+                .and_then(|result| result.map_err(|_| EscrowError::TransferFailed));
+            result.map(|_| ())
+        }
+
+        // Counts a failed transfer and pauses the token once it keeps failing.
+        // Only the sweeps record failures: they skip the token and succeed, while a
+        // message returning the error would revert the count along with it.
+        fn record_failed_transfer(&mut self, token: AccountId) {
+            let failures = self.get_failed_transfers(token).saturating_add(1);
+            self.failed_transfers.insert(token, &failures);
+            if failures >= AUTO_PAUSE_THRESHOLD && !self.is_token_paused(token) {
+                self.token_paused.insert(token, &true);
+                self.emit_event(AutoPaused { token });
+            }
+        }
+
+        fn action_id(action: &AdminAction) -> Hash {
+            let mut output = [0u8; 32];
+            ink::env::hash_encoded::<Blake2x256, _>(action, &mut output);
+            Hash::from(output)
+        }
+
+        fn consume_timelock(&mut self, action: &AdminAction) -> Result<(), EscrowError> {
+            // without a delay admin actions can be executed directly
+            if self.delay == 0 {
+                return Ok(());
+            }
+
+            let id = Escrow::action_id(action);
+            let earliest = self
+                .queued_actions
+                .get(id)
+                .ok_or(EscrowError::ActionNotQueued)?;
+            if self.env().block_timestamp() < earliest {
+                return Err(EscrowError::TimelockNotElapsed);
+            }
+            self.queued_actions.remove(id);
+            Ok(())
+        }
+
+        // books the withdraw fee on `amount` and returns what is left to send out
+        fn retain_withdraw_fee(
+            &mut self,
+            token: AccountId,
+            amount: Balance,
+        ) -> Result<Balance, EscrowError> {
+            let fee = mul_div(
+                amount,
+                Balance::from(self.withdraw_fee_bps),
+                Balance::from(MAX_BPS),
+            )
+            .ok_or(EscrowError::Overflow)?;
+            if fee > 0 {
+                let fees = self
+                    .get_withdraw_fees(token)
+                    .checked_add(fee)
+                    .ok_or(EscrowError::Overflow)?;
+                self.withdraw_fees.insert(token, &fees);
+            }
+            Ok(amount - fee)
+        }
+
+        // amount already withdrawn in the window covering `now`
+        fn withdrawn_in_window(&self, now: Timestamp) -> Balance {
+            if now >= self.period_start.saturating_add(self.period) {
+                0
+            } else {
+                self.withdrawn_in_period
+            }
+        }
+
+        fn check_rate_limit(&self, amount: Balance) -> Result<(), EscrowError> {
+            if self.max_withdraw_per_period == 0 {
+                return Ok(());
+            }
+            let withdrawn = self.withdrawn_in_window(self.env().block_timestamp());
+            if withdrawn.saturating_add(amount) > self.max_withdraw_per_period {
+                return Err(EscrowError::RateLimited);
+            }
+            Ok(())
+        }
+
+        fn record_withdrawal(&mut self, amount: Balance) {
+            if self.max_withdraw_per_period == 0 {
+                return;
+            }
+            let now = self.env().block_timestamp();
+            let withdrawn = self.withdrawn_in_window(now);
+            // a fresh window starts with the first withdrawal after the previous one ended
+            if withdrawn == 0 {
+                self.period_start = now;
+            }
+            self.withdrawn_in_period = withdrawn + amount;
+        }
+
+        fn ensure_depositor(&self, depositor: AccountId) -> Result<(), EscrowError> {
+            if self.permissioned && !self.is_depositor_allowed(depositor) {
+                return Err(EscrowError::NotAuthorized);
+            }
+            Ok(())
+        }
+
+        fn ensure_token_active(&self, token: AccountId) -> Result<(), EscrowError> {
+            if self.is_token_paused(token) {
+                return Err(EscrowError::TokenPaused);
+            }
+            Ok(())
+        }
+
+        fn count_withdraw(&mut self, user: AccountId) {
+            let withdrawals = self.get_withdraw_count(user);
+            self.withdraw_count.insert(user, &(withdrawals + 1));
         }
 
         #[ink(constructor)]
-        pub fn new(supported_tokens: Vec<AccountId>) -> Self {
+        pub fn new(
+            supported_tokens: Vec<AccountId>,
+            delay: Timestamp,
+            permissioned: bool,
+            emit_events: bool,
+        ) -> Self {
             Self {
                 tokens: supported_tokens,
                 balances: Mapping::new(),
                 admin: Self::env().caller(),
+                delay,
+                queued_actions: Mapping::new(),
+                deposit_receipts: Mapping::new(),
+                next_receipt_id: 0,
+                withdraw_fee_bps: 0,
+                withdraw_fees: Mapping::new(),
+                deposit_count: Mapping::new(),
+                withdraw_count: Mapping::new(),
+                max_withdraw_per_period: 0,
+                period: 0,
+                period_start: 0,
+                withdrawn_in_period: 0,
+                token_paused: Mapping::new(),
+                deposited: Mapping::new(),
+                operator: None,
+                deposit_caps: Mapping::new(),
+                token_metadata: Mapping::new(),
+                failed_transfers: Mapping::new(),
+                pool_token: None,
+                pool_balance: 0,
+                pool_shares: Mapping::new(),
+                total_pool_shares: 0,
+                permissioned,
+                depositor_allowlist: Mapping::new(),
+                emit_events,
             }
         }
 
@@ -85,131 +428,1582 @@ mod escrow {
             self.tokens.clone()
         }
 
+        /// Returns each supported token's (balance, deposit cap, paused) in one call.
+        /// Uncapped tokens report a cap of `Balance::MAX`.
+        #[ink(message)]
+        pub fn tokens_overview(&self) -> Vec<(AccountId, Balance, Balance, bool)> {
+            self.tokens
+                .iter()
+                .map(|token| {
+                    (
+                        *token,
+                        self.get_balance(*token),
+                        self.get_deposit_cap(*token).unwrap_or(Balance::MAX),
+                        self.is_token_paused(*token),
+                    )
+                })
+                .collect()
+        }
+
         #[ink(message)]
         pub fn get_balance(&self, token: AccountId) -> Balance {
-            self.balances.get(&token).unwrap_or_default()
+            self.balances.get(token).unwrap_or_default()
+        }
+
+        /// Fetches and stores the name and symbol of a supported token, so UIs can
+        /// show them without calling the token on every render.
+        #[ink(message)]
+        pub fn cache_token_metadata(&mut self, token: AccountId) -> Result<TokenMetadata, EscrowError> {
+            if !self.tokens.contains(&token) {
+                return Err(EscrowError::UnsupportedToken);
+            }
+            let name: String =
+                self.invoke_token(token, ExecutionInput::new(Selector::new(GET_NAME_SELECTOR)))?;
+            let symbol: String =
+                self.invoke_token(token, ExecutionInput::new(Selector::new(GET_SYMBOL_SELECTOR)))?;
+            let metadata = (name, symbol);
+            self.token_metadata.insert(token, &metadata);
+            Ok(metadata)
         }
 
         #[ink(message)]
-        pub fn deposit(&mut self, token: AccountId, amount: Balance) -> Result<(), EscrowError> {
+        pub fn get_token_metadata(&self, token: AccountId) -> Option<TokenMetadata> {
+            self.token_metadata.get(token)
+        }
+
+        /// Remaining approval `owner` has granted the escrow on `token`.
+        ///
+        /// Queries the token's `allowance(owner, spender)` message (selector 7), so it
+        /// relies on the approve/allowance support `FungibleToken` has for this; tokens
+        /// without it report 0.
+        #[ink(message)]
+        pub fn escrow_allowance(&self, token: AccountId, owner: AccountId) -> Balance {
+            self.invoke_token(
+                token,
+                ExecutionInput::new(Selector::new(ALLOWANCE_SELECTOR))
+                    .push_arg(owner)
+                    .push_arg(self.env().account_id()),
+            )
+            .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn deposit(&mut self, token: AccountId, amount: Balance) -> Result<u64, EscrowError> {
             let caller = self.env().caller();
+            self.ensure_depositor(caller)?;
 
             // Check if the token is supported
             if !self.tokens.contains(&token) {
                 return Err(EscrowError::UnsupportedToken);
             }
-
-            // Get the selector for the transfer function
-            let transfer_selector = Escrow::calculate_selector("transfer");
-            let transfer_selector = Selector::new(transfer_selector);
-
-            build_call::<DefaultEnvironment>()
-                .call(token)
-                .gas_limit(0)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(transfer_selector)
-                        .push_arg(caller)
-                        .push_arg(amount),
-                )
-                .returns::<()>()
-                .invoke();
+            self.ensure_token_active(token)?;
+            if amount > self.deposit_capacity_remaining(token) {
+                return Err(EscrowError::DepositCapExceeded);
+            }
 
             // Emit the deposit event
-            self.env().emit_event(Deposit { token, amount });
+            self.emit_event(Deposit { token, amount });
 
-            // Update the balances
+            // Update the balances before any external call (checks-effects-interactions)
             let balance = self.get_balance(token);
             self.balances.insert(token, &(balance + amount));
+
+            let deposited = self.get_deposited(token, caller);
+            self.deposited.insert((token, caller), &(deposited + amount));
+
+            // Issue the receipt that redeems exactly this deposit
+            let id = self.next_receipt_id;
+            self.deposit_receipts.insert(id, &(token, caller, amount));
+            self.next_receipt_id += 1;
+
+            let deposits = self.get_deposit_count(caller);
+            self.deposit_count.insert(caller, &(deposits + 1));
+
+            // Pull the tokens from the depositor, who must have approved the escrow
+            self.transfer_token_from(token, caller, self.env().account_id(), amount)?;
+            Ok(id)
+        }
+
+        #[ink(message)]
+        pub fn get_pool_token(&self) -> Option<AccountId> {
+            self.pool_token
+        }
+
+        #[ink(message)]
+        pub fn set_pool_token(&mut self, token: Option<AccountId>) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can choose the pooled token
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+            if token.is_some_and(|token| !self.tokens.contains(&token)) {
+                return Err(EscrowError::UnsupportedToken);
+            }
+            // switching tokens would strand the shares already issued
+            if self.total_pool_shares > 0 {
+                return Err(EscrowError::SharesOutstanding);
+            }
+            self.pool_token = token;
             Ok(())
         }
 
         #[ink(message)]
-        pub fn withdraw(&mut self, token: AccountId, amount: Balance) -> Result<(), EscrowError> {
+        pub fn get_pool_shares(&self, user: AccountId) -> Balance {
+            self.pool_shares.get(user).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_total_pool_shares(&self) -> Balance {
+            self.total_pool_shares
+        }
+
+        #[ink(message)]
+        pub fn get_pool_balance(&self) -> Balance {
+            self.pool_balance
+        }
+
+        /// Deposits `amount` of the pool token and mints pool shares in proportion
+        /// to the pool it joins, returning the shares minted.
+        #[ink(message)]
+        pub fn deposit_for_shares(&mut self, amount: Balance) -> Result<Balance, EscrowError> {
             let caller = self.env().caller();
-            // only the admin can withdraw
+            self.ensure_depositor(caller)?;
+            let token = self.pool_token.ok_or(EscrowError::PoolNotSet)?;
+            self.ensure_token_active(token)?;
+
+            // the first depositor sets the price at one share per token
+            let shares = if self.total_pool_shares == 0 {
+                amount
+            } else {
+                amount
+                    .checked_mul(self.total_pool_shares)
+                    .ok_or(EscrowError::Overflow)?
+                    / self.pool_balance
+            };
+            // a deposit too small to buy a share would be lost to the pool
+            if shares == 0 {
+                return Err(EscrowError::InsufficientShares);
+            }
+            self.emit_event(Deposit { token, amount });
+            self.pool_balance += amount;
+            self.total_pool_shares += shares;
+            let owned = self.get_pool_shares(caller);
+            self.pool_shares.insert(caller, &(owned + shares));
+
+            self.transfer_token_from(token, caller, self.env().account_id(), amount)?;
+            Ok(shares)
+        }
+
+        /// Adds `amount` of the pool token to the pool without minting shares, so
+        /// every outstanding share is worth more, e.g. to pass on a yield.
+        #[ink(message)]
+        pub fn fund_pool(&mut self, amount: Balance) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can fund the pool
             if caller != self.admin {
                 return Err(EscrowError::TransferFailed);
             }
+            let token = self.pool_token.ok_or(EscrowError::PoolNotSet)?;
+            self.ensure_token_active(token)?;
+            // without shares the next depositor would get the funds for free
+            if self.total_pool_shares == 0 {
+                return Err(EscrowError::InsufficientShares);
+            }
+            self.emit_event(Deposit { token, amount });
+            self.pool_balance = self
+                .pool_balance
+                .checked_add(amount)
+                .ok_or(EscrowError::Overflow)?;
 
-            // Check if the token is supported
-            if !self.tokens.contains(&token) {
-                return Err(EscrowError::UnsupportedToken);
+            self.transfer_token_from(token, caller, self.env().account_id(), amount)?;
+            Ok(())
+        }
+
+        /// Burns `shares` of the caller's pool shares for their pro-rata slice of
+        /// the pool, returning the amount paid out.
+        #[ink(message)]
+        pub fn redeem_shares(&mut self, shares: Balance) -> Result<Balance, EscrowError> {
+            let caller = self.env().caller();
+            let token = self.pool_token.ok_or(EscrowError::PoolNotSet)?;
+            self.ensure_token_active(token)?;
+            let owned = self.get_pool_shares(caller);
+            if owned < shares {
+                return Err(EscrowError::InsufficientShares);
             }
 
-            // Check if the balance is sufficient
-            let balance = self.get_balance(token);
-            if balance < amount {
-                return Err(EscrowError::InsufficientBalance);
+            // rounds down so the pool never pays out more than it holds
+            let amount = shares
+                .checked_mul(self.pool_balance)
+                .ok_or(EscrowError::Overflow)?
+                / self.total_pool_shares;
+            // shares worth less than one token would be burnt for nothing
+            if amount == 0 {
+                return Err(EscrowError::InsufficientShares);
             }
+            self.emit_event(Withdrawal { token, amount });
+            self.pool_balance -= amount;
+            self.total_pool_shares -= shares;
+            self.pool_shares.insert(caller, &(owned - shares));
 
-            // Get the selector for the transfer function
-            let transfer_selector = Escrow::calculate_selector("transfer");
-            let transfer_selector = Selector::new(transfer_selector);
+            self.transfer_token(token, caller, amount)?;
+            Ok(amount)
+        }
 
-            build_call::<DefaultEnvironment>()
-                .call(token)
-                .gas_limit(0)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(transfer_selector)
-                        .push_arg(caller)
-                        .push_arg(amount),
-                )
-                .returns::<()>()
-                .invoke();
+        #[ink(message)]
+        pub fn get_deposited(&self, token: AccountId, user: AccountId) -> Balance {
+            self.deposited.get((token, user)).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn is_permissioned(&self) -> bool {
+            self.permissioned
+        }
+
+        #[ink(message)]
+        pub fn is_depositor_allowed(&self, depositor: AccountId) -> bool {
+            self.depositor_allowlist.get(depositor).unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn set_depositor_allowed(&mut self, depositor: AccountId, allowed: bool) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can manage the depositor allowlist
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+            if allowed {
+                self.depositor_allowlist.insert(depositor, &true);
+            } else {
+                self.depositor_allowlist.remove(depositor);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_operator(&self) -> Option<AccountId> {
+            self.operator
+        }
+
+        #[ink(message)]
+        pub fn set_operator(&mut self, operator: Option<AccountId>) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can set the operator
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+            self.operator = operator;
+            Ok(())
+        }
+
+        /// Sends `amount` of `from`'s deposit of `token` to the operator, which
+        /// spends it on `from`'s behalf, e.g. as collateral for an ETF vault.
+        #[ink(message)]
+        pub fn pull_deposit(
+            &mut self,
+            token: AccountId,
+            from: AccountId,
+            amount: Balance,
+        ) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the operator can pull deposits
+            if self.operator != Some(caller) {
+                return Err(EscrowError::TransferFailed);
+            }
+            self.ensure_token_active(token)?;
+
+            let balance = self.get_balance(token);
+            let deposited = self.get_deposited(token, from);
+            if balance < amount || deposited < amount {
+                return Err(EscrowError::InsufficientBalance);
+            }
 
-            // Update the balances
             self.balances.insert(token, &(balance - amount));
+            self.deposited.insert((token, from), &(deposited - amount));
+            self.transfer_token(token, caller, amount)?;
             Ok(())
         }
 
         #[ink(message)]
-        pub fn withdraw_all(&mut self) -> Result<(), EscrowError> {
+        pub fn get_deposit_cap(&self, token: AccountId) -> Option<Balance> {
+            self.deposit_caps.get(token)
+        }
+
+        #[ink(message)]
+        pub fn set_deposit_cap(&mut self, token: AccountId, cap: Option<Balance>) -> Result<(), EscrowError> {
             let caller = self.env().caller();
-            // only the admin can withdraw
+            // only the admin can cap deposits
             if caller != self.admin {
                 return Err(EscrowError::TransferFailed);
             }
+            if let Some(cap) = cap {
+                self.deposit_caps.insert(token, &cap);
+            } else {
+                self.deposit_caps.remove(token);
+            }
+            Ok(())
+        }
 
-            for token in self.tokens.iter() {
-                let balance = self.get_balance(*token);
-                if balance > 0 {
-                    // Get the selector for the transfer function
-                    let transfer_selector = Escrow::calculate_selector("transfer");
-                    let transfer_selector = Selector::new(transfer_selector);
-
-                    build_call::<DefaultEnvironment>()
-                        .call(*token)
-                        .gas_limit(0)
-                        .transferred_value(0)
-                        .exec_input(
-                            ExecutionInput::new(transfer_selector)
-                                .push_arg(caller)
-                                .push_arg(balance),
-                        )
-                        .returns::<()>()
-                        .invoke();
-
-                    // Update the balances
-                    self.balances.insert(*token, &0);
-                }
+        /// Returns how much more of `token` can be deposited before hitting its cap.
+        #[ink(message)]
+        pub fn deposit_capacity_remaining(&self, token: AccountId) -> Balance {
+            match self.get_deposit_cap(token) {
+                Some(cap) => cap.saturating_sub(self.get_balance(token)),
+                None => Balance::MAX,
+            }
+        }
+
+        #[ink(message)]
+        pub fn get_deposit_count(&self, user: AccountId) -> u32 {
+            self.deposit_count.get(user).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_withdraw_count(&self, user: AccountId) -> u32 {
+            self.withdraw_count.get(user).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_deposit_receipt(&self, id: u64) -> Option<DepositReceipt> {
+            self.deposit_receipts.get(id)
+        }
+
+        #[ink(message)]
+        pub fn withdraw_receipt(&mut self, id: u64) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            let (token, depositor, amount) = self
+                .deposit_receipts
+                .get(id)
+                .ok_or(EscrowError::InvalidReceipt)?;
+            if caller != depositor {
+                return Err(EscrowError::NotDepositor);
+            }
+            self.ensure_token_active(token)?;
+
+            // Check if the balance is sufficient, the operator may have spent the deposit
+            let balance = self.get_balance(token);
+            let deposited = self.get_deposited(token, caller);
+            if balance < amount || deposited < amount {
+                return Err(EscrowError::InsufficientBalance);
             }
-            self.env().emit_event(WithdrawAll {});
+
+            // A receipt can only be redeemed once
+            self.deposit_receipts.remove(id);
+
+            // Update the balances
+            self.balances.insert(token, &(balance - amount));
+            self.deposited.insert((token, caller), &(deposited - amount));
+            self.count_withdraw(caller);
+
+            self.transfer_token(token, caller, amount)?;
             Ok(())
         }
 
         #[ink(message)]
-        pub fn get_admin(&self) -> AccountId {
-            self.admin
+        pub fn withdraw(&mut self, token: AccountId, amount: Balance) -> Result<(), EscrowError> {
+            self.withdraw_net(token, amount).map(|_| ())
         }
 
+        /// Withdraws up to `amount` of `token`, returning what was sent after the
+        /// withdraw fee.
         #[ink(message)]
-        pub fn set_admin(&mut self, new_admin: AccountId) {
+        pub fn withdraw_up_to(&mut self, token: AccountId, amount: Balance) -> Result<Balance, EscrowError> {
+            // Clamp to the balance instead of failing with InsufficientBalance
+            let amount = amount.min(self.get_balance(token));
+            self.withdraw_net(token, amount)
+        }
+
+        // withdraws `amount` of `token` to the admin, returning the amount sent
+        fn withdraw_net(&mut self, token: AccountId, amount: Balance) -> Result<Balance, EscrowError> {
             let caller = self.env().caller();
-            if caller == self.admin {
-                self.admin = new_admin;
+            // only the admin can withdraw
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+
+            // Check if the token is supported
+            if !self.tokens.contains(&token) {
+                return Err(EscrowError::UnsupportedToken);
+            }
+            self.ensure_token_active(token)?;
+
+            // Check if the balance is sufficient
+            let balance = self.get_balance(token);
+            if balance < amount {
+                return Err(EscrowError::InsufficientBalance);
             }
+
+            self.check_rate_limit(amount)?;
+            self.consume_timelock(&AdminAction::Withdraw { token, amount })?;
+
+            // Update the balances
+            self.balances.insert(token, &(balance - amount));
+            self.record_withdrawal(amount);
+            self.count_withdraw(caller);
+
+            let net = self.retain_withdraw_fee(token, amount)?;
+            self.transfer_token(token, caller, net)?;
+            Ok(net)
+        }
+
+        #[ink(message)]
+        pub fn withdraw_split(
+            &mut self,
+            token: AccountId,
+            recipients: Vec<AccountId>,
+            amounts: Vec<Balance>,
+        ) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can withdraw
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+
+            // Check if the token is supported
+            if !self.tokens.contains(&token) {
+                return Err(EscrowError::UnsupportedToken);
+            }
+            self.ensure_token_active(token)?;
+
+            if recipients.len() != amounts.len() {
+                return Err(EscrowError::LengthMismatch);
+            }
+
+            // Check if the balance covers the whole split
+            let balance = self.get_balance(token);
+            let total = amounts
+                .iter()
+                .try_fold(0 as Balance, |total, amount| total.checked_add(*amount))
+                .ok_or(EscrowError::InsufficientBalance)?;
+            if balance < total {
+                return Err(EscrowError::InsufficientBalance);
+            }
+
+            self.check_rate_limit(total)?;
+            self.consume_timelock(&AdminAction::WithdrawSplit {
+                token,
+                recipients: recipients.clone(),
+                amounts: amounts.clone(),
+            })?;
+
+            // Update the balances
+            self.balances.insert(token, &(balance - total));
+            self.record_withdrawal(total);
+            self.count_withdraw(caller);
+
+            // every recipient pays the withdraw fee on their own share
+            let mut payouts = Vec::new();
+            for amount in amounts {
+                payouts.push(self.retain_withdraw_fee(token, amount)?);
+            }
+            for (recipient, amount) in recipients.into_iter().zip(payouts) {
+                self.transfer_token(token, recipient, amount)?;
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn withdraw_all(&mut self) -> Result<(), EscrowError> {
+            self.sweep(self.tokens.clone(), AdminAction::WithdrawAll)
+        }
+
+        /// Sweeps `limit` tokens of the token list from `start`, so a list too long
+        /// for one `withdraw_all` can be emptied in chunks.
+        #[ink(message)]
+        pub fn withdraw_range(&mut self, start: u32, limit: u32) -> Result<(), EscrowError> {
+            let tokens = self
+                .tokens
+                .iter()
+                .skip(start as usize)
+                .take(limit as usize)
+                .copied()
+                .collect();
+            self.sweep(tokens, AdminAction::WithdrawRange { start, limit })
+        }
+
+        // withdraws the whole balance of each of `tokens` to the admin
+        fn sweep(&mut self, tokens: Vec<AccountId>, action: AdminAction) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can withdraw
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+
+            // paused tokens stay in the escrow instead of holding back the others
+            let (tokens, paused): (Vec<_>, Vec<_>) = tokens
+                .into_iter()
+                .partition(|token| self.ensure_token_active(*token).is_ok());
+            let total = tokens
+                .iter()
+                .fold(0 as Balance, |total, token| total.saturating_add(self.get_balance(*token)));
+            self.check_rate_limit(total)?;
+            self.consume_timelock(&action)?;
+
+            // Update the balances before sending anything out
+            let mut payouts = Vec::new();
+            for token in tokens {
+                let balance = self.get_balance(token);
+                if balance > 0 {
+                    self.balances.insert(token, &0);
+                    payouts.push((token, balance, self.retain_withdraw_fee(token, balance)?));
+                }
+            }
+            match action {
+                AdminAction::WithdrawRange { start, limit } => {
+                    self.emit_info_event(WithdrawRange { start, limit })
+                }
+                _ => self.emit_info_event(WithdrawAll {}),
+            }
+            for token in paused {
+                self.emit_event(WithdrawSkipped { token });
+            }
+            self.record_withdrawal(total);
+            self.count_withdraw(caller);
+
+            for (token, balance, amount) in payouts {
+                // a stuck token must not hold back the others, so undo its
+                // bookkeeping and leave it in the escrow
+                if self.transfer_token(token, caller, amount).is_err() {
+                    self.balances.insert(token, &balance);
+                    let fees = self.get_withdraw_fees(token);
+                    self.withdraw_fees.insert(token, &(fees - (balance - amount)));
+                    self.withdrawn_in_period = self.withdrawn_in_period.saturating_sub(balance);
+                    self.record_failed_transfer(token);
+                    self.emit_event(WithdrawSkipped { token });
+                }
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_withdraw_fee_bps(&self) -> u16 {
+            self.withdraw_fee_bps
+        }
+
+        #[ink(message)]
+        pub fn set_withdraw_fee_bps(&mut self, fee_bps: u16) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can set the withdraw fee
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+            if fee_bps > MAX_BPS {
+                return Err(EscrowError::InvalidFee);
+            }
+            self.withdraw_fee_bps = fee_bps;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_token_paused(&self, token: AccountId) -> bool {
+            self.token_paused.get(token).unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn set_token_paused(&mut self, token: AccountId, paused: bool) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can pause a token
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+            if paused {
+                self.token_paused.insert(token, &true);
+            } else {
+                self.token_paused.remove(token);
+                // unpausing re-arms the circuit breaker
+                self.failed_transfers.remove(token);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_failed_transfers(&self, token: AccountId) -> u32 {
+            self.failed_transfers.get(token).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn get_withdraw_fees(&self, token: AccountId) -> Balance {
+            self.withdraw_fees.get(token).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn claim_withdraw_fees(&mut self, token: AccountId) -> Result<Balance, EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can claim the withdraw fees
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+
+            let fees = self.get_withdraw_fees(token);
+            if fees > 0 {
+                self.withdraw_fees.insert(token, &0);
+                self.transfer_token(token, caller, fees)?;
+            }
+            Ok(fees)
+        }
+
+        #[ink(message)]
+        pub fn get_withdraw_limit(&self) -> (Balance, Timestamp) {
+            (self.max_withdraw_per_period, self.period)
+        }
+
+        #[ink(message)]
+        pub fn set_withdraw_limit(
+            &mut self,
+            max_per_period: Balance,
+            period: Timestamp,
+        ) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can change the withdraw limit
+            if caller != self.admin {
+                return Err(EscrowError::TransferFailed);
+            }
+
+            // loosening the cap goes through the timelock like any other admin action
+            self.consume_timelock(&AdminAction::SetWithdrawLimit {
+                max_per_period,
+                period,
+            })?;
+            self.max_withdraw_per_period = max_per_period;
+            self.period = period;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn version(&self) -> u32 {
+            VERSION
+        }
+
+        #[ink(message)]
+        pub fn get_admin(&self) -> AccountId {
+            self.admin
+        }
+
+        #[ink(message)]
+        pub fn set_admin(&mut self, new_admin: AccountId) -> Result<(), EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can change the admin
+            if caller != self.admin {
+                return Err(EscrowError::NotAuthorized);
+            }
+            self.consume_timelock(&AdminAction::SetAdmin { new_admin })?;
+            self.admin = new_admin;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_delay(&self) -> Timestamp {
+            self.delay
+        }
+
+        #[ink(message)]
+        pub fn queue_action(&mut self, action: AdminAction) -> Result<Timestamp, EscrowError> {
+            let caller = self.env().caller();
+            // only the admin can queue actions
+            if caller != self.admin {
+                return Err(EscrowError::NotAuthorized);
+            }
+
+            let earliest = self
+                .env()
+                .block_timestamp()
+                .checked_add(self.delay)
+                .ok_or(EscrowError::Overflow)?;
+            self.queued_actions
+                .insert(Escrow::action_id(&action), &earliest);
+            Ok(earliest)
+        }
+
+        #[ink(message)]
+        pub fn execute_action(&mut self, action: AdminAction) -> Result<(), EscrowError> {
+            match action {
+                AdminAction::Withdraw { token, amount } => self.withdraw(token, amount),
+                AdminAction::WithdrawSplit {
+                    token,
+                    recipients,
+                    amounts,
+                } => self.withdraw_split(token, recipients, amounts),
+                AdminAction::WithdrawAll => self.withdraw_all(),
+                AdminAction::WithdrawRange { start, limit } => self.withdraw_range(start, limit),
+                AdminAction::SetAdmin { new_admin } => self.set_admin(new_admin),
+                AdminAction::SetWithdrawLimit {
+                    max_per_period,
+                    period,
+                } => self.set_withdraw_limit(max_per_period, period),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::mock_erc20;
+
+        // escrow deployed at django holding `amount` of a single mock token deposited by alice.
+        fn funded_escrow(amount: Balance) -> (Escrow, AccountId) {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            mock_erc20::mint(token, accounts.alice, amount);
+            mock_erc20::approve(token, accounts.alice, accounts.django, amount);
+            escrow.deposit(token, amount).unwrap();
+            (escrow, token)
+        }
+
+        #[ink::test]
+        fn timelocked_set_admin_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut escrow = Escrow::new(Vec::new(), 100, false, true);
+            let action = AdminAction::SetAdmin { new_admin: accounts.bob };
+
+            // direct calls are rejected until the action is queued and the delay elapsed
+            assert_eq!(escrow.set_admin(accounts.bob), Err(EscrowError::ActionNotQueued));
+            assert_eq!(escrow.get_admin(), accounts.alice);
+            assert_eq!(
+                escrow.execute_action(action.clone()),
+                Err(EscrowError::ActionNotQueued)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            assert_eq!(escrow.queue_action(action.clone()), Ok(110));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(109);
+            assert_eq!(
+                escrow.execute_action(action.clone()),
+                Err(EscrowError::TimelockNotElapsed)
+            );
+            assert_eq!(escrow.set_admin(accounts.bob), Err(EscrowError::TimelockNotElapsed));
+            assert_eq!(escrow.get_admin(), accounts.alice);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(110);
+            assert_eq!(escrow.execute_action(action.clone()), Ok(()));
+            assert_eq!(escrow.get_admin(), accounts.bob);
+
+            // a queued action can only be executed once
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.execute_action(action), Err(EscrowError::ActionNotQueued));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(escrow.set_admin(accounts.charlie), Err(EscrowError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn timelocked_withdraw_all_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut escrow = Escrow::new(Vec::new(), 50, false, true);
+
+            assert_eq!(escrow.withdraw_all(), Err(EscrowError::ActionNotQueued));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                escrow.queue_action(AdminAction::WithdrawAll),
+                Err(EscrowError::NotAuthorized)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(escrow.queue_action(AdminAction::WithdrawAll), Ok(50));
+            assert_eq!(escrow.withdraw_all(), Err(EscrowError::TimelockNotElapsed));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(50);
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+        }
+
+        #[ink::test]
+        fn deposit_pulls_approved_tokens() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 60);
+            assert_eq!(escrow.escrow_allowance(token, accounts.alice), 60);
+
+            assert_eq!(escrow.deposit(token, 60), Ok(0));
+            assert_eq!(escrow.get_balance(token), 60);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 40);
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 60);
+            assert_eq!(escrow.escrow_allowance(token, accounts.alice), 0);
+
+            assert_eq!(escrow.deposit(token, 10), Err(EscrowError::TransferFailed));
+            // the bookkeeping is written before the call, so only the error reverting
+            // it on-chain undoes it; off-chain just check that no tokens moved
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 40);
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 60);
+
+            assert_eq!(
+                escrow.deposit(accounts.eve, 1),
+                Err(EscrowError::UnsupportedToken)
+            );
+        }
+
+        #[ink::test]
+        fn withdraw_pays_admin() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            escrow.deposit(token, 100).unwrap();
+
+            assert_eq!(escrow.withdraw(token, 30), Ok(()));
+            assert_eq!(escrow.get_balance(token), 70);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 30);
+
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+            assert_eq!(escrow.get_balance(token), 0);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 100);
+        }
+
+        #[ink::test]
+        fn rejected_transfers_are_reported() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            escrow.deposit(token, 50).unwrap();
+
+            mock_erc20::reject_transfers(token);
+            assert_eq!(escrow.deposit(token, 50), Err(EscrowError::TransferFailed));
+            assert_eq!(escrow.withdraw(token, 50), Err(EscrowError::TransferFailed));
+            assert_eq!(escrow.get_balance(token), 50);
+        }
+
+        #[ink::test]
+        fn version_works() {
+            let escrow = Escrow::new(Vec::new(), 0, false, true);
+            assert_eq!(escrow.version(), 1);
+        }
+
+        #[ink::test]
+        fn withdraw_split_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+
+            assert_eq!(
+                escrow.withdraw_split(token, vec![accounts.bob, accounts.charlie], vec![30, 50]),
+                Ok(())
+            );
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 30);
+            assert_eq!(mock_erc20::balance_of(token, accounts.charlie), 50);
+            assert_eq!(escrow.get_balance(token), 20);
+        }
+
+        #[ink::test]
+        fn withdraw_split_rejects_invalid_splits() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+
+            assert_eq!(
+                escrow.withdraw_split(token, vec![accounts.bob, accounts.charlie], vec![60, 50]),
+                Err(EscrowError::InsufficientBalance)
+            );
+            assert_eq!(
+                escrow.withdraw_split(token, vec![accounts.bob], vec![10, 20]),
+                Err(EscrowError::LengthMismatch)
+            );
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 0);
+            assert_eq!(escrow.get_balance(token), 100);
+        }
+
+        #[ink::test]
+        fn withdraw_receipt_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            mock_erc20::mint(token, accounts.bob, 100);
+            mock_erc20::approve(token, accounts.bob, accounts.django, 100);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let first = escrow.deposit(token, 30).unwrap();
+            let second = escrow.deposit(token, 50).unwrap();
+            assert_ne!(first, second);
+            assert_eq!(escrow.get_deposit_receipt(first), Some((token, accounts.bob, 30)));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(escrow.withdraw_receipt(first), Err(EscrowError::NotDepositor));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.withdraw_receipt(first), Ok(()));
+            assert_eq!(escrow.get_balance(token), 50);
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 50);
+            assert_eq!(escrow.get_deposit_receipt(first), None);
+
+            // a receipt cannot be redeemed twice
+            assert_eq!(escrow.withdraw_receipt(first), Err(EscrowError::InvalidReceipt));
+            assert_eq!(escrow.get_balance(token), 50);
+        }
+
+        #[ink::test]
+        fn withdraw_fee_is_retained() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(1000);
+            assert_eq!(escrow.set_withdraw_fee_bps(10_001), Err(EscrowError::InvalidFee));
+            assert_eq!(escrow.set_withdraw_fee_bps(250), Ok(()));
+
+            // 2.5% of 400 stays in the escrow
+            assert_eq!(escrow.withdraw(token, 400), Ok(()));
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 390);
+            assert_eq!(escrow.get_balance(token), 600);
+            assert_eq!(escrow.get_withdraw_fees(token), 10);
+
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 975);
+            assert_eq!(escrow.get_balance(token), 0);
+            assert_eq!(escrow.get_withdraw_fees(token), 25);
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 25);
+
+            assert_eq!(escrow.claim_withdraw_fees(token), Ok(25));
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 1000);
+            assert_eq!(escrow.get_withdraw_fees(token), 0);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.set_withdraw_fee_bps(0), Err(EscrowError::TransferFailed));
+            assert_eq!(escrow.claim_withdraw_fees(token), Err(EscrowError::TransferFailed));
+        }
+
+        #[ink::test]
+        fn history_counters_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+            assert_eq!(escrow.get_deposit_count(accounts.alice), 1);
+            assert_eq!(escrow.get_withdraw_count(accounts.alice), 0);
+
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            escrow.deposit(token, 10).unwrap();
+            assert_eq!(escrow.get_deposit_count(accounts.alice), 2);
+
+            escrow.withdraw(token, 10).unwrap();
+            escrow.withdraw_all().unwrap();
+            assert_eq!(escrow.get_withdraw_count(accounts.alice), 2);
+
+            // failed withdrawals are not counted
+            assert_eq!(escrow.withdraw(token, 1), Err(EscrowError::InsufficientBalance));
+            assert_eq!(escrow.get_withdraw_count(accounts.alice), 2);
+            assert_eq!(escrow.get_deposit_count(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn withdrawals_are_rate_limited() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(1000);
+            assert_eq!(escrow.set_withdraw_limit(100, 60), Ok(()));
+            assert_eq!(escrow.get_withdraw_limit(), (100, 60));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1000);
+            assert_eq!(escrow.withdraw(token, 70), Ok(()));
+            assert_eq!(escrow.withdraw(token, 40), Err(EscrowError::RateLimited));
+            assert_eq!(
+                escrow.withdraw_split(token, vec![accounts.bob], vec![40]),
+                Err(EscrowError::RateLimited)
+            );
+            assert_eq!(escrow.withdraw(token, 30), Ok(()));
+            assert_eq!(escrow.withdraw_all(), Err(EscrowError::RateLimited));
+
+            // still inside the window that started at 1000
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1059);
+            assert_eq!(escrow.withdraw(token, 1), Err(EscrowError::RateLimited));
+
+            // the next period starts afresh
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1060);
+            assert_eq!(escrow.withdraw(token, 100), Ok(()));
+            assert_eq!(escrow.withdraw(token, 1), Err(EscrowError::RateLimited));
+            assert_eq!(escrow.get_balance(token), 800);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 200);
+        }
+
+        #[ink::test]
+        fn withdraw_up_to_clamps_to_balance() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+
+            assert_eq!(escrow.withdraw_up_to(token, 30), Ok(30));
+            assert_eq!(escrow.get_balance(token), 70);
+            assert_eq!(escrow.withdraw_up_to(token, 500), Ok(70));
+            assert_eq!(escrow.get_balance(token), 0);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 100);
+
+            assert_eq!(escrow.withdraw_up_to(accounts.eve, 1), Err(EscrowError::UnsupportedToken));
+        }
+
+        #[ink::test]
+        fn withdraw_all_skips_failing_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let tokens = [
+                AccountId::from([0x10; 32]),
+                AccountId::from([0x11; 32]),
+                AccountId::from([0x12; 32]),
+            ];
+            let mut escrow = Escrow::new(tokens.to_vec(), 0, false, true);
+            for token in tokens {
+                mock_erc20::mint(token, accounts.alice, 50);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 50);
+                escrow.deposit(token, 50).unwrap();
+            }
+            mock_erc20::reject_transfers(tokens[1]);
+
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+            for token in [tokens[0], tokens[2]] {
+                assert_eq!(escrow.get_balance(token), 0);
+                assert_eq!(mock_erc20::balance_of(token, accounts.alice), 50);
+            }
+            // the failing token is left where it was
+            assert_eq!(escrow.get_balance(tokens[1]), 50);
+            assert_eq!(mock_erc20::balance_of(tokens[1], accounts.django), 50);
+
+            type Event = <Escrow as ink::reflect::ContractEventBase>::Type;
+            let skipped: Vec<_> = ink::env::test::recorded_events()
+                .filter_map(|event| match <Event as scale::Decode>::decode(&mut &event.data[..]) {
+                    Ok(Event::WithdrawSkipped(WithdrawSkipped { token })) => Some(token),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(skipped, vec![tokens[1]]);
+        }
+
+        #[ink::test]
+        fn paused_token_is_blocked() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut escrow = Escrow::new(vec![first, second], 0, false, true);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+                escrow.deposit(token, 50).unwrap();
+            }
+
+            assert_eq!(escrow.set_token_paused(first, true), Ok(()));
+            assert!(escrow.is_token_paused(first));
+            assert!(!escrow.is_token_paused(second));
+            assert_eq!(escrow.deposit(first, 10), Err(EscrowError::TokenPaused));
+            assert_eq!(escrow.withdraw(first, 10), Err(EscrowError::TokenPaused));
+
+            // the other token keeps working
+            assert_eq!(escrow.deposit(second, 10), Ok(2));
+            assert_eq!(escrow.withdraw(second, 10), Ok(()));
+
+            assert_eq!(escrow.set_token_paused(first, false), Ok(()));
+            assert_eq!(escrow.withdraw(first, 10), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.set_token_paused(second, true), Err(EscrowError::TransferFailed));
+        }
+
+        #[ink::test]
+        fn operator_pulls_deposit() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+            assert_eq!(escrow.get_deposited(token, accounts.alice), 100);
+            assert_eq!(escrow.get_deposited(token, accounts.bob), 0);
+
+            // nobody can pull before an operator is set
+            assert_eq!(
+                escrow.pull_deposit(token, accounts.alice, 10),
+                Err(EscrowError::TransferFailed)
+            );
+            assert_eq!(escrow.set_operator(Some(accounts.eve)), Ok(()));
+            assert_eq!(escrow.get_operator(), Some(accounts.eve));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(escrow.pull_deposit(token, accounts.alice, 60), Ok(()));
+            assert_eq!(mock_erc20::balance_of(token, accounts.eve), 60);
+            assert_eq!(escrow.get_deposited(token, accounts.alice), 40);
+            assert_eq!(escrow.get_balance(token), 40);
+            assert_eq!(
+                escrow.pull_deposit(token, accounts.alice, 41),
+                Err(EscrowError::InsufficientBalance)
+            );
+
+            // the receipt no longer has a deposit to redeem
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(escrow.withdraw_receipt(0), Err(EscrowError::InsufficientBalance));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.set_operator(None), Err(EscrowError::TransferFailed));
+        }
+
+        #[ink::test]
+        fn deposit_cap_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(30);
+            assert_eq!(escrow.deposit_capacity_remaining(token), Balance::MAX);
+
+            assert_eq!(escrow.set_deposit_cap(token, Some(100)), Ok(()));
+            assert_eq!(escrow.get_deposit_cap(token), Some(100));
+            assert_eq!(escrow.deposit_capacity_remaining(token), 70);
+
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            assert_eq!(escrow.deposit(token, 71), Err(EscrowError::DepositCapExceeded));
+            escrow.deposit(token, 50).unwrap();
+            assert_eq!(escrow.deposit_capacity_remaining(token), 20);
+
+            // lowering the cap below the balance leaves no room rather than underflowing
+            assert_eq!(escrow.set_deposit_cap(token, Some(10)), Ok(()));
+            assert_eq!(escrow.deposit_capacity_remaining(token), 0);
+
+            assert_eq!(escrow.set_deposit_cap(token, None), Ok(()));
+            assert_eq!(escrow.deposit_capacity_remaining(token), Balance::MAX);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.set_deposit_cap(token, Some(1)), Err(EscrowError::TransferFailed));
+        }
+
+        #[ink::test]
+        fn balances_are_updated_before_token_calls() {
+            use std::{cell::RefCell, rc::Rc};
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(100);
+
+            // record the escrow's bookkeeping as each token call starts
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let log = seen.clone();
+            mock_erc20::on_invoke(move |_| {
+                let view = Escrow::new(Vec::new(), 0, false, true);
+                log.borrow_mut()
+                    .push((view.get_balance(token), view.get_deposited(token, accounts.alice)));
+            });
+
+            mock_erc20::mint(token, accounts.alice, 20);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+            escrow.deposit(token, 20).unwrap();
+            assert_eq!(seen.borrow().last(), Some(&(120, 120)));
+
+            escrow.withdraw(token, 50).unwrap();
+            assert_eq!(seen.borrow().last(), Some(&(70, 120)));
+
+            escrow.withdraw_receipt(1).unwrap();
+            assert_eq!(seen.borrow().last(), Some(&(50, 100)));
+
+            escrow.withdraw_all().unwrap();
+            assert_eq!(seen.borrow().last(), Some(&(0, 100)));
+        }
+
+        #[ink::test]
+        fn token_metadata_is_cached() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            mock_erc20::set_metadata(token, "MyToken", "MTK");
+            assert_eq!(escrow.get_token_metadata(token), None);
+
+            let metadata = ("MyToken".to_string(), "MTK".to_string());
+            assert_eq!(escrow.cache_token_metadata(token), Ok(metadata.clone()));
+            // later reads come from storage even if the token changes its answer
+            mock_erc20::set_metadata(token, "Renamed", "RNM");
+            assert_eq!(escrow.get_token_metadata(token), Some(metadata));
+
+            assert_eq!(
+                escrow.cache_token_metadata(accounts.eve),
+                Err(EscrowError::UnsupportedToken)
+            );
+        }
+
+        #[ink::test]
+        fn repeated_failures_auto_pause_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut escrow = Escrow::new(vec![first, second], 0, false, true);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 50);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 50);
+                escrow.deposit(token, 50).unwrap();
+            }
+            mock_erc20::reject_transfers(second);
+
+            for failures in 1..=3 {
+                assert!(!escrow.is_token_paused(second));
+                assert_eq!(escrow.withdraw_all(), Ok(()));
+                assert_eq!(escrow.get_failed_transfers(second), failures);
+            }
+            assert!(escrow.is_token_paused(second));
+            assert!(!escrow.is_token_paused(first));
+            assert_eq!(escrow.get_failed_transfers(first), 0);
+
+            type Event = <Escrow as ink::reflect::ContractEventBase>::Type;
+            let paused: Vec<_> = ink::env::test::recorded_events()
+                .filter_map(|event| match <Event as scale::Decode>::decode(&mut &event.data[..]) {
+                    Ok(Event::AutoPaused(AutoPaused { token })) => Some(token),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(paused, vec![second]);
+
+            // unpausing starts the count over
+            assert_eq!(escrow.set_token_paused(second, false), Ok(()));
+            assert_eq!(escrow.get_failed_transfers(second), 0);
+        }
+
+        #[ink::test]
+        fn withdraw_range_sweeps_in_chunks() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let tokens: Vec<_> = (0..5u8).map(|i| AccountId::from([0x10 + i; 32])).collect();
+            let mut escrow = Escrow::new(tokens.clone(), 0, false, true);
+            for token in tokens.iter() {
+                mock_erc20::mint(*token, accounts.alice, 50);
+                mock_erc20::approve(*token, accounts.alice, accounts.django, 50);
+                escrow.deposit(*token, 50).unwrap();
+            }
+
+            assert_eq!(escrow.withdraw_range(0, 3), Ok(()));
+            for token in &tokens[..3] {
+                assert_eq!(escrow.get_balance(*token), 0);
+                assert_eq!(mock_erc20::balance_of(*token, accounts.alice), 50);
+            }
+            for token in &tokens[3..] {
+                assert_eq!(escrow.get_balance(*token), 50);
+            }
+
+            // a range running past the end of the list stops there
+            assert_eq!(escrow.withdraw_range(3, 10), Ok(()));
+            for token in tokens.iter() {
+                assert_eq!(escrow.get_balance(*token), 0);
+                assert_eq!(mock_erc20::balance_of(*token, accounts.alice), 50);
+            }
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.withdraw_range(0, 1), Err(EscrowError::TransferFailed));
+        }
+
+        #[ink::test]
+        fn pool_shares_are_proportional() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            assert_eq!(escrow.deposit_for_shares(10), Err(EscrowError::PoolNotSet));
+            assert_eq!(
+                escrow.set_pool_token(Some(AccountId::from([0x11; 32]))),
+                Err(EscrowError::UnsupportedToken)
+            );
+            assert_eq!(escrow.set_pool_token(Some(token)), Ok(()));
+            for user in [accounts.alice, accounts.bob] {
+                mock_erc20::mint(token, user, 100);
+                mock_erc20::approve(token, user, accounts.django, 100);
+            }
+
+            assert_eq!(escrow.deposit_for_shares(100), Ok(100));
+            assert_eq!(escrow.set_pool_token(None), Err(EscrowError::SharesOutstanding));
+            // the pool doubles in value, as if it had earned a yield
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            assert_eq!(escrow.fund_pool(100), Ok(()));
+            assert_eq!(escrow.get_total_pool_shares(), 100);
+
+            // bob's 50 now buy half as many shares
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.deposit_for_shares(50), Ok(25));
+            assert_eq!(escrow.get_total_pool_shares(), 125);
+            assert_eq!(escrow.get_pool_balance(), 250);
+            // the pool is not part of the admin's balances
+            assert_eq!(escrow.get_balance(token), 0);
+
+            assert_eq!(escrow.redeem_shares(26), Err(EscrowError::InsufficientShares));
+            assert_eq!(escrow.fund_pool(10), Err(EscrowError::TransferFailed));
+            assert_eq!(escrow.redeem_shares(0), Err(EscrowError::InsufficientShares));
+            assert_eq!(escrow.redeem_shares(25), Ok(50));
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 100);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(escrow.redeem_shares(100), Ok(200));
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 200);
+            assert_eq!(escrow.get_total_pool_shares(), 0);
+            assert_eq!(escrow.get_pool_balance(), 0);
+        }
+
+        #[ink::test]
+        fn permissioned_deposit_requires_allowlist() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, true, true);
+            assert!(escrow.is_permissioned());
+            for depositor in [accounts.alice, accounts.bob] {
+                mock_erc20::mint(token, depositor, 100);
+                mock_erc20::approve(token, depositor, accounts.django, 100);
+            }
+
+            assert_eq!(escrow.deposit(token, 10), Err(EscrowError::NotAuthorized));
+            assert_eq!(escrow.set_depositor_allowed(accounts.alice, true), Ok(()));
+            assert!(escrow.is_depositor_allowed(accounts.alice));
+            assert_eq!(escrow.deposit(token, 10), Ok(0));
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 10);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(escrow.deposit(token, 10), Err(EscrowError::NotAuthorized));
+            // only the admin manages the allowlist
+            assert_eq!(
+                escrow.set_depositor_allowed(accounts.bob, true),
+                Err(EscrowError::TransferFailed)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(escrow.set_depositor_allowed(accounts.alice, false), Ok(()));
+            assert_eq!(escrow.deposit(token, 10), Err(EscrowError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn open_escrow_accepts_any_depositor() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut escrow = Escrow::new(vec![token], 0, false, true);
+            assert!(!escrow.is_permissioned());
+            mock_erc20::mint(token, accounts.bob, 100);
+            mock_erc20::approve(token, accounts.bob, accounts.django, 100);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(!escrow.is_depositor_allowed(accounts.bob));
+            assert_eq!(escrow.deposit(token, 10), Ok(0));
+        }
+
+        #[ink::test]
+        fn disabled_events_skip_informational_ones() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let tokens = [AccountId::from([0x10; 32]), AccountId::from([0x11; 32])];
+            let mut escrow = Escrow::new(tokens.to_vec(), 0, false, false);
+            for token in tokens {
+                mock_erc20::mint(token, accounts.alice, 50);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 50);
+                escrow.deposit(token, 50).unwrap();
+            }
+            mock_erc20::reject_transfers(tokens[1]);
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+
+            // the token transfers already record the sweep, but the skipped token is
+            // only known to the escrow
+            type Event = <Escrow as ink::reflect::ContractEventBase>::Type;
+            let events: Vec<_> = ink::env::test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data"))
+                .collect();
+            assert_eq!(events.len(), 3);
+            assert!(matches!(events[0], Event::Deposit(_)));
+            assert!(matches!(events[1], Event::Deposit(_)));
+            assert!(matches!(events[2], Event::WithdrawSkipped(WithdrawSkipped { token }) if token == tokens[1]));
+        }
+
+        #[ink::test]
+        fn tokens_overview_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut escrow = Escrow::new(vec![first, second], 0, false, true);
+            mock_erc20::mint(first, accounts.alice, 30);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 30);
+            escrow.deposit(first, 30).unwrap();
+            escrow.set_deposit_cap(first, Some(50)).unwrap();
+            escrow.set_token_paused(second, true).unwrap();
+
+            assert_eq!(
+                escrow.tokens_overview(),
+                vec![(first, 30, 50, false), (second, 0, Balance::MAX, true)]
+            );
+        }
+
+        #[ink::test]
+        fn queue_action_rejects_overflowing_delay() {
+            let mut escrow = Escrow::new(Vec::new(), Timestamp::MAX, false, true);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1);
+            assert_eq!(
+                escrow.queue_action(AdminAction::WithdrawAll),
+                Err(EscrowError::Overflow)
+            );
+            assert_eq!(escrow.withdraw_all(), Err(EscrowError::ActionNotQueued));
+        }
+
+        #[ink::test]
+        fn withdraw_fee_applies_to_every_withdrawal() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut escrow, token) = funded_escrow(1000);
+            assert_eq!(escrow.set_withdraw_fee_bps(1000), Ok(()));
+
+            // withdraw_up_to reports what was sent, not what was debited
+            assert_eq!(escrow.withdraw_up_to(token, 200), Ok(180));
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 180);
+            assert_eq!(escrow.get_balance(token), 800);
+
+            assert_eq!(
+                escrow.withdraw_split(token, vec![accounts.bob, accounts.charlie], vec![100, 50]),
+                Ok(())
+            );
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 90);
+            assert_eq!(mock_erc20::balance_of(token, accounts.charlie), 45);
+            assert_eq!(escrow.get_balance(token), 650);
+            assert_eq!(escrow.get_withdraw_fees(token), 35);
+        }
+
+        #[ink::test]
+        fn paused_token_blocks_every_withdrawal() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut escrow = Escrow::new(vec![first, second], 0, false, true);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 50);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 50);
+                escrow.deposit(token, 50).unwrap();
+            }
+            assert_eq!(escrow.set_token_paused(first, true), Ok(()));
+
+            assert_eq!(
+                escrow.withdraw_split(first, vec![accounts.bob], vec![10]),
+                Err(EscrowError::TokenPaused)
+            );
+            assert_eq!(escrow.withdraw_receipt(0), Err(EscrowError::TokenPaused));
+
+            // sweeps leave the paused token in the escrow and take the rest
+            assert_eq!(escrow.withdraw_range(0, 1), Ok(()));
+            assert_eq!(escrow.get_balance(first), 50);
+            assert_eq!(escrow.withdraw_all(), Ok(()));
+            assert_eq!(escrow.get_balance(first), 50);
+            assert_eq!(escrow.get_balance(second), 0);
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 0);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 50);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use fee_token::FeeTokenRef;
+        use fungibleToken::{Erc20 as _, FungibleTokenRef};
+        use ink_e2e::build_message;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn escrow_allowance_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let escrow_constructor = EscrowRef::new(vec![token], 0, false, true);
+            let escrow = client
+                .instantiate("escrow", &ink_e2e::alice(), escrow_constructor, 0, None)
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+
+            // approve only part of alice's balance
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(escrow, 300, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let allowance = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.escrow_allowance(token, alice));
+            let allowance = client
+                .call_dry_run(&ink_e2e::alice(), &allowance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(allowance, 300);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn token_metadata_is_cached(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let escrow_constructor = EscrowRef::new(vec![token], 0, false, true);
+            let escrow = client
+                .instantiate("escrow", &ink_e2e::alice(), escrow_constructor, 0, None)
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+
+            let cache = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.cache_token_metadata(token));
+            client
+                .call(&ink_e2e::alice(), cache, 0, None)
+                .await
+                .expect("cache_token_metadata failed");
+
+            let metadata = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.get_token_metadata(token));
+            let metadata = client
+                .call_dry_run(&ink_e2e::alice(), &metadata, 0, None)
+                .await
+                .return_value();
+            assert_eq!(metadata, Some(("MyToken".to_string(), "MTK".to_string())));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fee_token/Cargo.toml")]
+        async fn auto_pause_survives_on_chain(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            // the stub token has no `transfer`, so every payout from the escrow fails
+            let token_constructor = FeeTokenRef::new(1000, 0);
+            let token = client
+                .instantiate("fee_token", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let escrow_constructor = EscrowRef::new(vec![token], 0, false, true);
+            let escrow = client
+                .instantiate("escrow", &ink_e2e::alice(), escrow_constructor, 0, None)
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+
+            let deposit = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.deposit(token, 100));
+            client
+                .call(&ink_e2e::alice(), deposit, 0, None)
+                .await
+                .expect("deposit failed");
+
+            // each sweep skips the token and succeeds, so the failure count is kept
+            for _ in 0..3 {
+                let withdraw_all = build_message::<EscrowRef>(escrow.clone())
+                    .call(|escrow| escrow.withdraw_all());
+                client
+                    .call(&ink_e2e::alice(), withdraw_all, 0, None)
+                    .await
+                    .expect("withdraw_all failed");
+            }
+
+            let paused = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.is_token_paused(token));
+            let paused = client
+                .call_dry_run(&ink_e2e::alice(), &paused, 0, None)
+                .await
+                .return_value();
+            assert!(paused);
+
+            let balance = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.get_balance(token));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 100);
+
+            Ok(())
         }
     }
 }
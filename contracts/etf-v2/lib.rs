@@ -1,5 +1,9 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+#[cfg(test)]
+#[path = "../mock_erc20.rs"]
+mod mock_erc20;
+
 #[ink::contract]
 mod etf_escrow {
 
@@ -8,12 +12,41 @@ mod etf_escrow {
         DefaultEnvironment,
     };
 
+    use ink::prelude::string::String;
     use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
 
-    // const shares per vault
+    use escrow::EscrowError;
+
+    // whole shares per vault, before scaling by the share decimals
     const SHARES: Balance = 100;
+    const MAX_BPS: u16 = 10_000;
+    // decimals `close_vault_normalized` reads the required balances in
+    const NORMALIZED_DECIMALS: u8 = 18;
+    const VERSION: u32 = 1;
+    const TRANSFER_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::transfer");
     const TRANSFER_FROM_SELECTOR: [u8; 4] = [0, 0, 0, 6];
+    const BALANCE_OF_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::balance_of");
+    const PRICE_OF_SELECTOR: [u8; 4] = ink::selector_bytes!("price_of");
+    const GET_DEPOSITED_SELECTOR: [u8; 4] = ink::selector_bytes!("get_deposited");
+    const PULL_DEPOSIT_SELECTOR: [u8; 4] = ink::selector_bytes!("pull_deposit");
+    const SWAP_SELECTOR: [u8; 4] = ink::selector_bytes!("swap");
+    const SHARES_PER_VAULT_FIELD: [u8; 16] = config_field(b"shares_per_vault");
+    const MIN_RATIO_FIELD: [u8; 16] = config_field(b"min_ratio_bps");
+    const MIN_HOLD_FIELD: [u8; 16] = config_field(b"min_hold");
+    const MIN_BASKET_FIELD: [u8; 16] = config_field(b"min_basket_value");
+    const SINGLE_PENALTY_FIELD: [u8; 16] = config_field(b"single_penalty");
+
+    // left-aligned, zero-padded field name for `ConfigChanged`
+    const fn config_field(name: &[u8]) -> [u8; 16] {
+        let mut field = [0u8; 16];
+        let mut i = 0;
+        while i < name.len() && i < field.len() {
+            field[i] = name[i];
+            i += 1;
+        }
+        field
+    }
 
     #[ink::trait_definition]
     pub trait Erc20 {
@@ -44,6 +77,9 @@ mod etf_escrow {
         vault: u8,
         #[ink(topic)]
         owner: AccountId,
+        // shares minted for the vault, including the protocol fee
+        shares: Balance,
+        block: BlockNumber,
     }
 
     #[ink(event)]
@@ -52,6 +88,75 @@ mod etf_escrow {
         vault: u8,
         #[ink(topic)]
         owner: AccountId,
+        // whose shares were burnt, which need not be the owner's
+        #[ink(topic)]
+        redeemer: AccountId,
+        shares_burned: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceUsed {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        spent: Balance,
+        remaining: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OperatorSet {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        approved: bool,
+    }
+
+    #[ink(event)]
+    pub struct ConfigChanged {
+        // zero-padded name of the changed setting
+        #[ink(topic)]
+        field: [u8; 16],
+        old: Balance,
+        new: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CollateralResynced {
+        #[ink(topic)]
+        token: AccountId,
+        old: Balance,
+        new: Balance,
+    }
+
+    #[ink(event)]
+    pub struct EmergencyWithdraw {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+        // internal collateral for `token` after resyncing it to the actual balance
+        collateral: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FeeCollected {
+        #[ink(topic)]
+        token: AccountId,
+        amount: Balance,
+        #[ink(topic)]
+        collector: AccountId,
     }
 
     #[derive(Debug, Clone, scale::Encode, scale::Decode, PartialEq)]
@@ -62,30 +167,446 @@ mod etf_escrow {
         TransferFailed,
         CloseVaultFailed,
         VaultAlreadyExists,
+        NotAuthorized,
+        Overflow,
+        InsufficientCollateral,
+        OracleNotSet,
+        TransferShortfall,
+        VaultsStillOpen,
+        PartialRedeemNotAllowed,
+        UndercollateralizedOpen,
+        /// A basket token transfer reverted, naming the token that failed.
+        TokenTransferFailed { token: AccountId },
+        ImportNotAllowed,
+        ZeroAddress,
+        NotAllowlisted,
+        VaultNotFound,
+        LengthMismatch,
+        CooldownActive,
+        BelowMinBasket,
+        PenaltyTooLow,
+        InvalidPenalty,
+        SwapFailed,
+        SlippageExceeded,
+        InvalidSharesPerVault,
+    }
+
+    #[derive(Debug, Clone, scale::Encode, scale::Decode, PartialEq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Stats {
+        pub vaults_quantity: u8,
+        pub total_supply: Balance,
+        pub required_token_count: u32,
+    }
+
+    /// Computes `a * b / denom` with checked arithmetic, returning `None` on overflow
+    /// or a zero `denom`.
+    ///
+    /// Every fee, weight and NAV division goes through this helper so rounding is
+    /// consistent: fees round down (in the user's favour) and redemption payouts
+    /// round down (against the user), so the escrow never pays out more than it holds.
+    fn mul_div(a: Balance, b: Balance, denom: Balance, round_up: bool) -> Option<Balance> {
+        let product = a.checked_mul(b)?;
+        let quotient = product.checked_div(denom)?;
+        if round_up && product % denom != 0 {
+            quotient.checked_add(1)
+        } else {
+            Some(quotient)
+        }
+    }
+
+    /// Splits `total` between `weights` pro rata, rounding each part down and handing
+    /// what rounding left over out one unit at a time to the parts that were rounded
+    /// down, so the parts add up to `total` and none exceeds its exact share rounded up.
+    fn split_pro_rata(total: Balance, weights: &[Balance]) -> Option<Vec<Balance>> {
+        let sum = weights.iter().try_fold(0, |sum: Balance, weight| sum.checked_add(*weight))?;
+        let mut parts = Vec::with_capacity(weights.len());
+        for weight in weights {
+            parts.push(mul_div(total, *weight, sum, false)?);
+        }
+        let mut left = total - parts.iter().sum::<Balance>();
+        for (part, weight) in parts.iter_mut().zip(weights) {
+            if left == 0 {
+                break;
+            }
+            if total.checked_mul(*weight)? % sum != 0 {
+                *part += 1;
+                left -= 1;
+            }
+        }
+        Some(parts)
+    }
+
+    /// Rescales `amount` from `from` decimals to `to` decimals, rounding down.
+    fn normalize_amount(amount: Balance, from: u8, to: u8) -> Option<Balance> {
+        if to >= from {
+            amount.checked_mul(Balance::from(10u8).checked_pow(u32::from(to - from))?)
+        } else {
+            mul_div(amount, 1, Balance::from(10u8).checked_pow(u32::from(from - to))?, false)
+        }
     }
 
     #[ink(storage)]
     pub struct EtfEscrow {
-        vaults_quantity: u8,
+        // ids are never reused, so this only grows while `live_vault_count` follows closes
+        next_vault_id: u8,
+        live_vault_count: u8,
         required_tokens: Vec<AccountId>,
         required_balances: Vec<Balance>,
         vaults: Mapping<u8, AccountId>,
         vaults_quantity_per_owner: Mapping<AccountId, u8>,
         balances: Mapping<AccountId, Balance>,
         total_supply: Balance,
+        admin: AccountId,
+        protocol_fee_bps: u16,
+        fee_recipient: AccountId,
+        token_labels: Mapping<AccountId, String>,
+        // external reference -> vault opened for it, so retries are idempotent
+        vault_refs: Mapping<[u8; 32], u8>,
+        vaults_of_owner: Mapping<AccountId, Vec<u8>>,
+        // share token metadata shown by wallets
+        share_name: String,
+        share_symbol: String,
+        share_decimals: u8,
+        // SHARES whole shares scaled by `share_decimals`
+        shares_per_vault: Balance,
+        // prices basket tokens in the reference currency
+        price_oracle: Option<AccountId>,
+        // minimum basket value per minted share, zero disables the check
+        min_collateral_ratio_bps: u16,
+        // set once vault records have been migrated in from another instance
+        vaults_imported: bool,
+        // `Escrow` whose deposits are drawn as collateral before the caller's wallet
+        escrow: Option<AccountId>,
+        // when set, shares only move between allowlisted accounts
+        enforce_allowlist: bool,
+        allowlist: Mapping<AccountId, bool>,
+        // redemption fee pulled from the redeemer in its own token, e.g. a stablecoin
+        fee_token: Option<AccountId>,
+        fee_amount: Balance,
+        // whether closing a vault pays its collateral to the vault owner instead of the caller
+        redeem_to_owner: bool,
+        // how long a vault must stay open before it can be closed, zero disables it
+        min_hold_duration: Timestamp,
+        // smallest oracle value of one vault's basket, or of each token's amount without an oracle
+        min_basket_value: Balance,
+        // vault -> earliest time it can be closed, fixed when it is opened
+        vault_unlocks_at: Mapping<u8, Timestamp>,
+        // (owner, operator) pairs where the operator may move and redeem all the owner's shares
+        operators: Mapping<(AccountId, AccountId), bool>,
+        // vault -> basket amounts pulled when it was opened, paid back when it is closed
+        vault_collateral: Mapping<u8, Vec<Balance>>,
+        // (owner, spender) -> shares the spender may still move or redeem for the owner
+        allowances: Mapping<(AccountId, AccountId), Balance>,
+        // (redeemed value threshold, fee bps), ascending by threshold
+        fee_tiers: Vec<(Balance, u16)>,
+        // smallest penalty a single-token redemption must accept
+        single_token_penalty_bps: u16,
+        // informational events are skipped when unset
+        emit_events: bool,
+        // treasury and the shares it was seeded with, which no vault backs
+        treasury: Option<(AccountId, Balance)>,
     }
 
     impl EtfEscrow {
+        // shares may only move between allowlisted accounts while the allowlist is enforced
+        fn ensure_allowed(&self, from: AccountId, to: AccountId) -> Result<(), ContractError> {
+            if self.enforce_allowlist && !(self.is_allowed(from) && self.is_allowed(to)) {
+                return Err(ContractError::NotAllowlisted);
+            }
+            Ok(())
+        }
+
+        // sending to the all-zeros account would burn shares without reducing the supply
+        fn ensure_non_zero(to: AccountId) -> Result<(), ContractError> {
+            if to == AccountId::from([0u8; 32]) {
+                return Err(ContractError::ZeroAddress);
+            }
+            Ok(())
+        }
+
+        // emits through `EmitEvent::<EtfEscrow>` explicitly: when another contract crate with
+        // events is linked in (as the e2e tests do), `self.env().emit_event` is ambiguous
+        fn emit_event<E>(&self, event: E)
+        where
+            E: Into<<EtfEscrow as ink::reflect::ContractEventBase>::Type>,
+        {
+            ink::codegen::EmitEvent::<EtfEscrow>::emit_event(self.env(), event);
+        }
+
+        // fees, operators, resyncs and config changes can be switched off; vault
+        // lifecycle, emergency withdrawals and share allowances always emit
+        fn emit_info_event<E>(&self, event: E)
+        where
+            E: Into<<EtfEscrow as ink::reflect::ContractEventBase>::Type>,
+        {
+            if self.emit_events {
+                self.emit_event(event);
+            }
+        }
+
+        fn invoke_token<Args, R>(
+            &self,
+            token: AccountId,
+            input: ExecutionInput<Args>,
+        ) -> Result<R, ContractError>
+        where
+            Args: scale::Encode,
+            R: scale::Decode,
+        {
+            let call = build_call::<DefaultEnvironment>()
+                .call(token)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(input)
+                .returns::<R>()
+                .params();
+
+            // a reverted token call is reported instead of being silently ignored
+            #[cfg(not(test))]
+            let result = call.try_invoke().ok().and_then(|result| result.ok());
+            #[cfg(test)]
+            let result = crate::mock_erc20::try_invoke(self.env().account_id(), &call);
+            result.ok_or(ContractError::TransferFailed)
+        }
+
+        fn transfer_token_from(
+            &self,
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<(), ContractError> {
+            let transfer_selector = Selector::new(TRANSFER_FROM_SELECTOR);
+
+            // basket transfers happen in loops, so report which token failed
+            self.invoke_token::<_, Result<Balance, ContractError>>(
+                token,
+                ExecutionInput::new(transfer_selector)
+                    .push_arg(from)
+                    .push_arg(to)
+                    .push_arg(amount),
+            )
+            .ok()
+            .and_then(|result| result.ok())
+            .map(|_| ())
+            .ok_or(ContractError::TokenTransferFailed { token })
+        }
+
+        // draws the collateral from `from`'s deposit in the linked escrow when it
+        // covers `amount`, from their wallet otherwise
+        fn pull_collateral(
+            &self,
+            token: AccountId,
+            from: AccountId,
+            amount: Balance,
+        ) -> Result<(), ContractError> {
+            if let Some(escrow) = self.escrow {
+                let deposited: Balance = self.invoke_token(
+                    escrow,
+                    ExecutionInput::new(Selector::new(GET_DEPOSITED_SELECTOR))
+                        .push_arg(token)
+                        .push_arg(from),
+                )?;
+                if deposited >= amount {
+                    return self
+                        .invoke_token::<_, Result<(), EscrowError>>(
+                            escrow,
+                            ExecutionInput::new(Selector::new(PULL_DEPOSIT_SELECTOR))
+                                .push_arg(token)
+                                .push_arg(from)
+                                .push_arg(amount),
+                        )
+                        .ok()
+                        .and_then(|result| result.ok())
+                        .ok_or(ContractError::TokenTransferFailed { token });
+                }
+            }
+            self.transfer_token_from(token, from, self.env().account_id(), amount)
+        }
+
+        fn escrow_token_balance(&self, token: AccountId) -> Result<Balance, ContractError> {
+            self.invoke_token(
+                token,
+                ExecutionInput::new(Selector::new(BALANCE_OF_SELECTOR))
+                    .push_arg(self.env().account_id()),
+            )
+        }
+
+        fn token_balance_of(&self, token: AccountId, owner: AccountId) -> Balance {
+            self.invoke_token(
+                token,
+                ExecutionInput::new(Selector::new(BALANCE_OF_SELECTOR)).push_arg(owner),
+            )
+            .unwrap_or_default()
+        }
+
+        fn oracle_price(&self, oracle: AccountId, token: AccountId) -> Result<Balance, ContractError> {
+            self.invoke_token(
+                oracle,
+                ExecutionInput::new(Selector::new(PRICE_OF_SELECTOR)).push_arg(token),
+            )
+        }
+
+        // values `amounts` of the basket tokens at oracle prices when available,
+        // one unit per token otherwise
+        fn basket_value(&self, amounts: &[Balance]) -> Result<Balance, ContractError> {
+            let mut value: Balance = 0;
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                let price = match self.price_oracle {
+                    Some(oracle) => self.oracle_price(oracle, *token)?,
+                    None => 1,
+                };
+                let token_value = amounts[i].checked_mul(price).ok_or(ContractError::Overflow)?;
+                value = value.checked_add(token_value).ok_or(ContractError::Overflow)?;
+            }
+            Ok(value)
+        }
+
+        // the lowest fee among the tiers `value` reaches, nothing below the first tier
+        fn redemption_fee_bps(&self, value: Balance) -> u16 {
+            self.fee_tiers
+                .iter()
+                .filter(|(threshold, _)| value >= *threshold)
+                .map(|(_, bps)| *bps)
+                .min()
+                .unwrap_or(0)
+        }
+
+        fn check_collateral_ratio(&self) -> Result<(), ContractError> {
+            if self.min_collateral_ratio_bps == 0 {
+                return Ok(());
+            }
+
+            let value = self.basket_value(&self.required_balances)?;
+            let required = mul_div(
+                self.shares_per_vault,
+                Balance::from(self.min_collateral_ratio_bps),
+                Balance::from(MAX_BPS),
+                true,
+            )
+            .ok_or(ContractError::Overflow)?;
+            if value < required {
+                return Err(ContractError::UndercollateralizedOpen);
+            }
+            Ok(())
+        }
+
+        fn check_min_basket(&self) -> Result<(), ContractError> {
+            if self.min_basket_value == 0 {
+                return Ok(());
+            }
+
+            if self.price_oracle.is_none() {
+                // without prices, every token amount must reach the minimum on its own
+                if self.required_balances.iter().any(|amount| *amount < self.min_basket_value) {
+                    return Err(ContractError::BelowMinBasket);
+                }
+                return Ok(());
+            }
+            if self.basket_value(&self.required_balances)? < self.min_basket_value {
+                return Err(ContractError::BelowMinBasket);
+            }
+            Ok(())
+        }
+
+        fn fee_shares(&self) -> Result<Balance, ContractError> {
+            mul_div(
+                self.shares_per_vault,
+                Balance::from(self.protocol_fee_bps),
+                Balance::from(MAX_BPS),
+                false,
+            )
+            .ok_or(ContractError::Overflow)
+        }
+
         #[ink(constructor)]
-        pub fn new(required_tokens: Vec<AccountId>, required_balances: Vec<Balance>) -> Self {
+        #[allow(clippy::too_many_arguments)]
+        pub fn new(
+            required_tokens: Vec<AccountId>,
+            required_balances: Vec<Balance>,
+            initial_treasury: Option<(AccountId, Balance)>,
+            protocol_fee_bps: u16,
+            fee_recipient: AccountId,
+            share_name: String,
+            share_symbol: String,
+            share_decimals: u8,
+            enforce_allowlist: bool,
+            redeem_to_owner: bool,
+            fee_tiers: Vec<(Balance, u16)>,
+            emit_events: bool,
+        ) -> Self {
+            assert!(protocol_fee_bps <= MAX_BPS, "protocol fee above 100%");
+            assert!(
+                fee_tiers.windows(2).all(|pair| pair[0].0 < pair[1].0),
+                "fee tiers must be sorted by threshold"
+            );
+            assert!(
+                fee_tiers.iter().all(|(_, bps)| *bps <= MAX_BPS),
+                "redemption fee above 100%"
+            );
+            assert!(
+                required_balances.iter().all(|balance| *balance > 0),
+                "required balance must be non-zero"
+            );
+            // the escrow's own shares cannot back themselves
+            assert!(
+                !required_tokens.contains(&Self::env().account_id()),
+                "basket cannot contain the escrow itself"
+            );
+            let shares_per_vault = Balance::from(10u8)
+                .checked_pow(u32::from(share_decimals))
+                .and_then(|unit| SHARES.checked_mul(unit))
+                .expect("share decimals too large");
+
+            let mut balances = Mapping::new();
+            let mut total_supply = 0;
+
+            // seed the treasury position with shares not backed by any vault; they
+            // can neither be redeemed nor moved, so they never claim others' collateral
+            if let Some((treasury, shares)) = initial_treasury {
+                balances.insert(treasury, &shares);
+                total_supply = shares;
+            }
+
             Self {
                 required_tokens,
                 required_balances,
-                vaults_quantity: 0,
+                next_vault_id: 0,
+                live_vault_count: 0,
                 vaults_quantity_per_owner: Mapping::new(),
-                balances: Mapping::new(),
+                balances,
                 vaults: Mapping::new(),
-                total_supply: 0,
+                total_supply,
+                admin: Self::env().caller(),
+                protocol_fee_bps,
+                fee_recipient,
+                token_labels: Mapping::new(),
+                vault_refs: Mapping::new(),
+                vaults_of_owner: Mapping::new(),
+                share_name,
+                share_symbol,
+                share_decimals,
+                shares_per_vault,
+                price_oracle: None,
+                min_collateral_ratio_bps: 0,
+                vaults_imported: false,
+                escrow: None,
+                enforce_allowlist,
+                allowlist: Mapping::new(),
+                fee_token: None,
+                fee_amount: 0,
+                redeem_to_owner,
+                min_hold_duration: 0,
+                min_basket_value: 0,
+                vault_unlocks_at: Mapping::new(),
+                operators: Mapping::new(),
+                vault_collateral: Mapping::new(),
+                allowances: Mapping::new(),
+                fee_tiers,
+                single_token_penalty_bps: 0,
+                emit_events,
+                treasury: initial_treasury,
             }
         }
 
@@ -94,187 +615,4211 @@ mod etf_escrow {
             self.required_tokens.clone()
         }
 
+        #[ink(message)]
+        pub fn required_tokens_count(&self) -> u32 {
+            self.required_tokens.len() as u32
+        }
+
+        #[ink(message)]
+        pub fn required_token_at(&self, i: u32) -> Option<AccountId> {
+            self.required_tokens.get(i as usize).copied()
+        }
+
         #[ink(message)]
         pub fn get_required_balances(&self) -> Vec<Balance> {
             self.required_balances.clone()
         }
 
+        /// The account `open_vault` pulls collateral with, and how much of each
+        /// required token it must be approved for per vault.
         #[ink(message)]
-        pub fn get_vault_owner(&self, vault: u8) -> AccountId {
-            self.vaults.get(&vault).unwrap()
+        pub fn open_instructions(&self) -> (AccountId, Vec<(AccountId, Balance)>) {
+            let amounts = self
+                .required_tokens
+                .iter()
+                .copied()
+                .zip(self.required_balances.iter().copied())
+                .collect();
+            (self.env().account_id(), amounts)
         }
 
         #[ink(message)]
-        pub fn get_vaults_quantity_per_owner(&self, owner: AccountId) -> u8 {
-            self.vaults_quantity_per_owner.get(&owner).unwrap_or(0)
+        pub fn set_token_label(&mut self, token: AccountId, label: String) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can label tokens
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if !self.required_tokens.contains(&token) {
+                return Err(ContractError::UnsupportedToken);
+            }
+
+            self.token_labels.insert(token, &label);
+            Ok(())
         }
 
         #[ink(message)]
-        pub fn get_vaults_quantity(&self) -> u8 {
-            self.vaults_quantity
+        pub fn get_token_labels(&self) -> Vec<(AccountId, String)> {
+            let mut labels = Vec::new();
+            for token in self.required_tokens.iter() {
+                if let Some(label) = self.token_labels.get(token) {
+                    labels.push((*token, label));
+                }
+            }
+            labels
         }
 
         #[ink(message)]
-        pub fn get_balance(&self, owner: AccountId) -> Balance {
-            self.balances.get(&owner).unwrap_or(0)
+        pub fn get_vault_owner(&self, vault: u8) -> Result<AccountId, ContractError> {
+            self.vaults.get(vault).ok_or(ContractError::VaultNotFound)
         }
 
         #[ink(message)]
-        pub fn open_vault(&mut self, owner: AccountId, vault: u8) -> Result<u8, ContractError> {
-            let caller = self.env().caller();
+        pub fn get_vault_owners(&self, vaults: Vec<u8>) -> Vec<Option<AccountId>> {
+            vaults.into_iter().map(|vault| self.vaults.get(vault)).collect()
+        }
 
-            if self.vaults.contains(&vault) {
-                return Err(ContractError::VaultAlreadyExists);
-            }
+        /// Returns whether `who` could close `vault` right now, so UIs can
+        /// disable redeeming without sending a failing transaction.
+        ///
+        /// Runs the same checks as `close_vault`: the vault exists and is past its
+        /// cooldown, `who` holds a vault's worth of redeemable shares and the escrow's
+        /// collateral covers the payout. Token transfers failing is not foreseen.
+        #[ink(message)]
+        pub fn can_close(&self, who: AccountId, vault: u8) -> bool {
+            self.ensure_closable(vault, who, &self.vault_payouts(vault)).is_ok()
+        }
 
-            for (i, token) in self.required_tokens.iter().enumerate() {
-                let balance = self.balances.get(token).unwrap_or(0);
-                if balance < self.required_balances[i] {
-                    return Err(ContractError::InsufficientBalance);
-                }
+        #[ink(message)]
+        pub fn get_vaults_quantity_per_owner(&self, owner: AccountId) -> u8 {
+            self.vaults_quantity_per_owner.get(owner).unwrap_or(0)
+        }
 
-                let transfer_selector =  Selector::new(TRANSFER_FROM_SELECTOR);
-
-                build_call::<DefaultEnvironment>()
-                    .call(*token)
-                    .gas_limit(0)
-                    .transferred_value(0)
-                    .exec_input(
-                        ExecutionInput::new(transfer_selector)
-                            .push_arg(caller)
-                            .push_arg(self.env().account_id())
-                            .push_arg(balance),
-                    )
-                    .returns::<()>()
-                    .invoke();
+        #[ink(message)]
+        pub fn get_vaults_of_owner(&self, owner: AccountId) -> Vec<u8> {
+            self.vaults_of_owner.get(owner).unwrap_or_default()
+        }
 
-                let escrow_balance = self.balances.get(token).unwrap_or(0);
-                self.balances
-                    .insert(token, &(escrow_balance + self.required_balances[i]));
-            }
+        #[ink(message)]
+        pub fn get_vaults_quantity(&self) -> u8 {
+            self.live_vault_count
+        }
 
-            let vault = self.vaults_quantity;
-            self.vaults.insert(vault, &owner);
-            self.vaults_quantity += 1;
-            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(&owner).unwrap_or(0);
-            self.vaults_quantity_per_owner
-                .insert(owner, &(vaults_quantity_of_owner + 1));
+        #[ink(message)]
+        pub fn get_next_vault_id(&self) -> u8 {
+            self.next_vault_id
+        }
 
-            // mint the etf tokens shares to the caller
-            let caller_balance = self.balances.get(caller).unwrap_or(0);
-            self.balances.insert(caller, &(caller_balance + SHARES));
+        #[ink(message)]
+        pub fn version(&self) -> u32 {
+            VERSION
+        }
 
-            self.env().emit_event(VaultOpened { vault, owner });
-            Ok(vault)
+        #[ink(message)]
+        pub fn share_decimals(&self) -> u8 {
+            self.share_decimals
         }
 
+        /// Raw share units per whole share, the denominator of fractional shares.
         #[ink(message)]
-        pub fn close_vault(&mut self, vault: u8) -> Result<(), ContractError> {
+        pub fn share_precision(&self) -> Balance {
+            // the constructor already checked this fits
+            Balance::from(10u8).pow(u32::from(self.share_decimals))
+        }
+
+        /// `owner`'s balance as whole shares and the raw units left over.
+        #[ink(message)]
+        pub fn whole_balance_of(&self, owner: AccountId) -> (Balance, Balance) {
+            let precision = self.share_precision();
+            let balance = self.balance_of(owner);
+            (balance / precision, balance % precision)
+        }
+
+        #[ink(message)]
+        pub fn shares_per_vault(&self) -> Balance {
+            self.shares_per_vault
+        }
+
+        #[ink(message)]
+        pub fn set_shares_per_vault(&mut self, n: Balance) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            let owner = self.vaults.get(&vault).unwrap();
+            // only the admin can change the shares minted per vault
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            // outstanding shares were minted at the old rate and must stay redeemable
+            if self.live_vault_count != 0 {
+                return Err(ContractError::VaultsStillOpen);
+            }
+            // a vault must mint at least one whole share
+            if n == 0 || !n.is_multiple_of(self.share_precision()) {
+                return Err(ContractError::InvalidSharesPerVault);
+            }
+            self.emit_info_event(ConfigChanged {
+                field: SHARES_PER_VAULT_FIELD,
+                old: self.shares_per_vault,
+                new: n,
+            });
+            self.shares_per_vault = n;
+            Ok(())
+        }
 
-            // check the caller has enough shares to close the vault and reedem the tokens
-            let caller_shares_balance = self.balances.get(caller).unwrap_or(0);
-            if caller_shares_balance < SHARES {
-                return Err(ContractError::InsufficientBalance);
+        #[ink(message)]
+        pub fn stats(&self) -> Stats {
+            Stats {
+                vaults_quantity: self.live_vault_count,
+                total_supply: self.total_supply,
+                required_token_count: self.required_tokens.len() as u32,
             }
+        }
 
-            let _ = self.balances.insert(caller, &(caller_shares_balance - SHARES));
-            let transfer_selector =  Selector::new(TRANSFER_FROM_SELECTOR);
+        #[ink(message)]
+        pub fn get_balance(&self, owner: AccountId) -> Balance {
+            self.balances.get(owner).unwrap_or(0)
+        }
 
+        #[ink(message)]
+        pub fn missing_collateral(&self, user: AccountId) -> Vec<(AccountId, Balance)> {
+            let mut missing = Vec::new();
             for (i, token) in self.required_tokens.iter().enumerate() {
-                // let balance = self.balances.get(token).unwrap_or(0);
-                build_call::<DefaultEnvironment>()
-                    .call(*token)
-                    .gas_limit(0)
-                    .transferred_value(0)
-                    .exec_input(
-                        ExecutionInput::new(transfer_selector)
-                            .push_arg(self.env().account_id())
-                            .push_arg(caller)
-                            .push_arg(self.required_balances[i]),
-                    )
-                    .returns::<()>()
-                    .invoke();
+                let held = self.token_balance_of(*token, user);
+                missing.push((*token, self.required_balances[i].saturating_sub(held)));
+            }
+            missing
+        }
 
-                let escrow_balance = self.balances.get(token).unwrap_or(0);
-                self.balances
-                    .insert(token, &(escrow_balance - self.required_balances[i]));
+        #[ink(message)]
+        pub fn preview_open(&self, _owner: AccountId) -> (u8, Balance, Vec<(AccountId, Balance)>) {
+            // (vault id, shares minted to the caller net of fees, collateral pulled)
+            let caller_shares = self.shares_per_vault - self.fee_shares().unwrap_or(0);
+            let collateral = self
+                .required_tokens
+                .iter()
+                .copied()
+                .zip(self.required_balances.iter().copied())
+                .collect();
+            (self.next_vault_id, caller_shares, collateral)
+        }
+
+        #[ink(message)]
+        pub fn get_vault_collateral(&self, vault: u8) -> Option<Vec<Balance>> {
+            self.vault_collateral.get(vault)
+        }
+
+        // shares `who` may redeem or move, leaving out the treasury's unbacked seed
+        fn free_shares(&self, who: AccountId) -> Balance {
+            let balance = self.balances.get(who).unwrap_or(0);
+            match self.treasury {
+                Some((treasury, seeded)) if treasury == who => balance.saturating_sub(seeded),
+                _ => balance,
             }
+        }
 
-            self.vaults.remove(&vault);
-            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(&owner).unwrap_or(0);
-            self.vaults_quantity_per_owner
-                .insert(owner, &(vaults_quantity_of_owner - 1));
-            self.env().emit_event(VaultClosed { vault, owner });
-            Ok(())
+        // what closing `vault` pays out; imported vaults have no snapshot and
+        // fall back to the current basket
+        fn vault_payouts(&self, vault: u8) -> Vec<Balance> {
+            self.vault_collateral
+                .get(vault)
+                .unwrap_or_else(|| self.required_balances.clone())
         }
 
-    }
+        // per-token sum of what closing each of `vaults` pays out
+        fn batch_payouts(&self, vaults: &[u8]) -> Result<Vec<Balance>, ContractError> {
+            let mut totals: Vec<Balance> = ink::prelude::vec![0; self.required_tokens.len()];
+            for vault in vaults {
+                for (total, amount) in totals.iter_mut().zip(self.vault_payouts(*vault)) {
+                    *total = total.checked_add(amount).ok_or(ContractError::Overflow)?;
+                }
+            }
+            Ok(totals)
+        }
 
-    impl Erc20 for EtfEscrow {
+        #[ink(message)]
+        pub fn preview_close_all(
+            &self,
+            who: AccountId,
+        ) -> Result<(Balance, Vec<(AccountId, Balance)>), ContractError> {
+            // (shares burnt, collateral paid out) if `who` closed every vault they own
+            let vaults = self.get_vaults_of_owner(who);
+            let collateral = self
+                .required_tokens
+                .iter()
+                .copied()
+                .zip(self.batch_payouts(&vaults)?)
+                .collect();
+            let shares = self
+                .shares_per_vault
+                .checked_mul(vaults.len() as Balance)
+                .ok_or(ContractError::Overflow)?;
+            Ok((shares, collateral))
+        }
 
         #[ink(message)]
-        fn get_name(&self) -> String {
-            "XTF-Index-0".to_string()
+        pub fn redeemable_vaults(
+            &self,
+            who: AccountId,
+        ) -> Result<(u32, Vec<(AccountId, Balance)>), ContractError> {
+            // (whole vaults `who`'s shares can close, their payout); `who`'s own vaults
+            // count at what closing them pays, any further ones at the current basket
+            let count = self.free_shares(who) / self.shares_per_vault;
+            let owned: Vec<u8> = self
+                .get_vaults_of_owner(who)
+                .into_iter()
+                .take(usize::try_from(count).unwrap_or(usize::MAX))
+                .collect();
+            let rest = count - owned.len() as Balance;
+            let mut collateral = Vec::with_capacity(self.required_tokens.len());
+            for ((token, amount), recorded) in self
+                .required_tokens
+                .iter()
+                .zip(self.required_balances.iter())
+                .zip(self.batch_payouts(&owned)?)
+            {
+                let amount = amount
+                    .checked_mul(rest)
+                    .and_then(|amount| amount.checked_add(recorded))
+                    .ok_or(ContractError::Overflow)?;
+                collateral.push((*token, amount));
+            }
+            let count = u32::try_from(count).map_err(|_| ContractError::Overflow)?;
+            Ok((count, collateral))
         }
 
         #[ink(message)]
-        fn get_symbol(&self) -> String {
-            "XTF".to_string()
+        pub fn get_price_oracle(&self) -> Option<AccountId> {
+            self.price_oracle
         }
 
         #[ink(message)]
-        fn balance_of(&self, owner: AccountId) -> Balance {
-            self.balances.get(&owner).unwrap_or(0)
+        pub fn set_price_oracle(&mut self, oracle: Option<AccountId>) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the price oracle
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.price_oracle = oracle;
+            Ok(())
         }
 
         #[ink(message)]
-        fn total_supply(&self) -> Balance {
-            self.total_supply
+        pub fn redeems_to_owner(&self) -> bool {
+            self.redeem_to_owner
         }
 
         #[ink(message)]
-        fn get_owner(&self) -> AccountId {
-            //  to be implemented
-            self.env().caller()
+        pub fn get_redemption_fee(&self) -> (Option<AccountId>, Balance) {
+            (self.fee_token, self.fee_amount)
         }
 
         #[ink(message)]
-        fn transfer(&mut self, to: AccountId, value: Balance) -> Result<Balance, ContractError> {
-            let from = self.env().caller();
-            let from_balance = self.balance_of(from);
-            if from_balance < value {
-                return Err(ContractError::InsufficientBalance);
+        pub fn set_redemption_fee(
+            &mut self,
+            fee_token: Option<AccountId>,
+            fee_amount: Balance,
+        ) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the redemption fee
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
             }
-            let to_balance = self.balance_of(to);
+            self.fee_token = fee_token;
+            self.fee_amount = fee_amount;
+            Ok(())
+        }
 
-            self.balances.insert(from, &(from_balance - value));
-            self.balances.insert(to, &(to_balance + value));
+        #[ink(message)]
+        pub fn get_escrow(&self) -> Option<AccountId> {
+            self.escrow
+        }
 
-            Ok(self.balance_of(from))
+        #[ink(message)]
+        pub fn set_escrow(&mut self, escrow: Option<AccountId>) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can link an escrow
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.escrow = escrow;
+            Ok(())
         }
 
-        #[ink(message, selector = 6)]
-        fn transfer_from(
-            &mut self,
-            from: AccountId,
-            to: AccountId,
-            value: Balance,
-        ) -> Result<Balance, ContractError> {
-            // let caller = self.env().caller();
-            // TO-DO: need to check if the caller is allowed to transfer from `from`
+        #[ink(message)]
+        pub fn is_allowed(&self, account: AccountId) -> bool {
+            self.allowlist.get(account).unwrap_or(false)
+        }
 
-            let from_balance = self.balance_of(from);
-            if from_balance < value {
-                return Err(ContractError::InsufficientBalance);
-            }
-            let to_balance = self.balance_of(to);
+        #[ink(message)]
+        pub fn is_allowlist_enforced(&self) -> bool {
+            self.enforce_allowlist
+        }
 
-            self.balances.insert(from, &(from_balance - value));
-            self.balances.insert(to, &(to_balance + value));
+        #[ink(message)]
+        pub fn set_allowed(&mut self, account: AccountId, allowed: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can manage the allowlist
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if allowed {
+                self.allowlist.insert(account, &true);
+            } else {
+                self.allowlist.remove(account);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_min_collateral_ratio_bps(&self) -> u16 {
+            self.min_collateral_ratio_bps
+        }
+
+        #[ink(message)]
+        pub fn set_min_collateral_ratio_bps(&mut self, ratio_bps: u16) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the collateral ratio
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.emit_info_event(ConfigChanged {
+                field: MIN_RATIO_FIELD,
+                old: Balance::from(self.min_collateral_ratio_bps),
+                new: Balance::from(ratio_bps),
+            });
+            self.min_collateral_ratio_bps = ratio_bps;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_fee_tiers(&self) -> Vec<(Balance, u16)> {
+            self.fee_tiers.clone()
+        }
+
+        #[ink(message)]
+        pub fn get_min_basket_value(&self) -> Balance {
+            self.min_basket_value
+        }
+
+        #[ink(message)]
+        pub fn set_min_basket_value(&mut self, value: Balance) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the minimum basket value
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.emit_info_event(ConfigChanged {
+                field: MIN_BASKET_FIELD,
+                old: self.min_basket_value,
+                new: value,
+            });
+            self.min_basket_value = value;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_operator(&self, owner: AccountId, operator: AccountId) -> bool {
+            self.operators.get((owner, operator)).unwrap_or(false)
+        }
+
+        /// Lets `operator` transfer and redeem all of the caller's shares, like an
+        /// ERC-721 approval for all.
+        #[ink(message)]
+        pub fn set_operator(&mut self, operator: AccountId, approved: bool) {
+            let owner = self.env().caller();
+            if approved {
+                self.operators.insert((owner, operator), &true);
+            } else {
+                self.operators.remove((owner, operator));
+            }
+            self.emit_info_event(OperatorSet { owner, operator, approved });
+        }
+
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get((owner, spender)).unwrap_or(0)
+        }
+
+        /// Lets `spender` move or redeem up to `value` of the caller's shares.
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), ContractError> {
+            let owner = self.env().caller();
+            self.set_allowance(owner, spender, value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<(), ContractError> {
+            let owner = self.env().caller();
+            let value = self
+                .allowance(owner, spender)
+                .checked_add(delta)
+                .ok_or(ContractError::Overflow)?;
+            self.set_allowance(owner, spender, value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<(), ContractError> {
+            let owner = self.env().caller();
+            let value = self
+                .allowance(owner, spender)
+                .checked_sub(delta)
+                .ok_or(ContractError::Overflow)?;
+            self.set_allowance(owner, spender, value);
+            Ok(())
+        }
+
+        fn set_allowance(&mut self, owner: AccountId, spender: AccountId, value: Balance) {
+            self.allowances.insert((owner, spender), &value);
+            self.emit_event(Approval { owner, spender, value });
+        }
+
+        // the owner and its operators act freely, anyone else spends down an allowance
+        fn spend_allowance(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+        ) -> Result<(), ContractError> {
+            if spender == owner || self.is_operator(owner, spender) {
+                return Ok(());
+            }
+            let allowance = self.allowance(owner, spender);
+            if allowance < value {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.allowances.insert((owner, spender), &(allowance - value));
+            self.emit_event(AllowanceUsed {
+                owner,
+                spender,
+                spent: value,
+                remaining: allowance - value,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_min_hold_duration(&self) -> Timestamp {
+            self.min_hold_duration
+        }
+
+        #[ink(message)]
+        pub fn set_min_hold_duration(&mut self, duration: Timestamp) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the cooldown
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            self.emit_info_event(ConfigChanged {
+                field: MIN_HOLD_FIELD,
+                old: Balance::from(self.min_hold_duration),
+                new: Balance::from(duration),
+            });
+            self.min_hold_duration = duration;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn vault_unlocks_at(&self, vault: u8) -> Option<Timestamp> {
+            self.vault_unlocks_at.get(vault)
+        }
+
+        /// Returns how well `vault` is backed, in basis points of what the open vaults
+        /// pay out when closed.
+        ///
+        /// Vaults share one collateral pool, so this is the pool's health spread evenly
+        /// over the open vaults: the worst covered basket token decides the ratio.
+        #[ink(message)]
+        pub fn vault_collateralization(&self, vault: u8) -> Result<u16, ContractError> {
+            if !self.vaults.contains(vault) {
+                return Err(ContractError::VaultNotFound);
+            }
+            let open_vaults: Vec<u8> = (0..self.next_vault_id)
+                .filter(|vault| self.vaults.contains(vault))
+                .collect();
+            let payouts = self.batch_payouts(&open_vaults)?;
+
+            let mut ratio_bps: Option<Balance> = None;
+            for (token, required) in self.required_tokens.iter().zip(payouts) {
+                // a token no open vault pays out cannot be short
+                if required == 0 {
+                    continue;
+                }
+                let held = self.balances.get(token).unwrap_or(0);
+                let token_ratio = mul_div(held, Balance::from(MAX_BPS), required, false)
+                    .ok_or(ContractError::Overflow)?;
+                ratio_bps = Some(ratio_bps.map_or(token_ratio, |ratio| ratio.min(token_ratio)));
+            }
+            let ratio_bps = ratio_bps.unwrap_or(Balance::from(MAX_BPS));
+            Ok(u16::try_from(ratio_bps).unwrap_or(u16::MAX))
+        }
+
+        /// Lists every open vault with its owner, for migrating to a new instance.
+        #[ink(message)]
+        pub fn export_vaults(&self) -> Result<Vec<(u8, AccountId)>, ContractError> {
+            // only the admin can export the vaults
+            if self.env().caller() != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            Ok((0..self.next_vault_id)
+                .filter_map(|vault| self.vaults.get(vault).map(|owner| (vault, owner)))
+                .collect())
+        }
+
+        /// Restores vault ownership records exported from a previous instance.
+        ///
+        /// Only allowed once, on an instance that has never opened a vault. Shares
+        /// and collateral are not part of the records and are migrated separately.
+        #[ink(message)]
+        pub fn import_vaults(&mut self, data: Vec<(u8, AccountId)>) -> Result<(), ContractError> {
+            // only the admin can import vaults
+            if self.env().caller() != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if self.vaults_imported || self.next_vault_id != 0 {
+                return Err(ContractError::ImportNotAllowed);
+            }
+            // check the whole batch upfront so it is imported entirely or not at all
+            for (i, (vault, _)) in data.iter().enumerate() {
+                if data[..i].iter().any(|(other, _)| other == vault) {
+                    return Err(ContractError::VaultAlreadyExists);
+                }
+            }
+            if data.iter().any(|(vault, _)| *vault == u8::MAX) {
+                return Err(ContractError::Overflow);
+            }
+
+            for (vault, owner) in data.iter() {
+                self.vaults.insert(*vault, owner);
+                let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(owner).unwrap_or(0);
+                self.vaults_quantity_per_owner
+                    .insert(owner, &(vaults_quantity_of_owner + 1));
+                let mut vaults_of_owner = self.get_vaults_of_owner(*owner);
+                vaults_of_owner.push(*vault);
+                self.vaults_of_owner.insert(owner, &vaults_of_owner);
+                // new vaults keep being numbered after the imported ones
+                self.next_vault_id = self.next_vault_id.max(*vault + 1);
+            }
+            // ids were checked unique above, so there is at most one entry per id
+            self.live_vault_count = data.len() as u8;
+            self.vaults_imported = true;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn nav_in_reference(&self) -> Result<Balance, ContractError> {
+            let oracle = self.price_oracle.ok_or(ContractError::OracleNotSet)?;
+
+            // value every basket token's collateral at the oracle price
+            let mut nav: Balance = 0;
+            for token in self.required_tokens.iter() {
+                let price = self.oracle_price(oracle, *token)?;
+                let value = self
+                    .balances
+                    .get(token)
+                    .unwrap_or(0)
+                    .checked_mul(price)
+                    .ok_or(ContractError::Overflow)?;
+                nav = nav.checked_add(value).ok_or(ContractError::Overflow)?;
+            }
+            Ok(nav)
+        }
+
+        #[ink(message)]
+        pub fn reconcile(&self) -> Vec<(AccountId, Balance, Balance)> {
+            // (token, internal collateral, actual balance_of(escrow)) to spot drift
+            let escrow = self.env().account_id();
+            self.required_tokens
+                .iter()
+                .map(|token| {
+                    let internal = self.balances.get(token).unwrap_or(0);
+                    (*token, internal, self.token_balance_of(*token, escrow))
+                })
+                .collect()
+        }
+
+        /// Total supply reported by `token`, for comparing NAV per share against it.
+        #[ink(message)]
+        pub fn external_supply(&self, token: AccountId) -> Balance {
+            let token: ink::contract_ref!(Erc20) = token.into();
+            token.total_supply()
+        }
+
+        /// Whether `token` answers a `balance_of` call made with the selectors this
+        /// escrow uses, so operators can check a token before adding it to a basket.
+        #[ink(message)]
+        pub fn probe_token(&self, token: AccountId) -> bool {
+            self.escrow_token_balance(token).is_ok()
+        }
+
+        /// Whether this contract owns `token`, and so may mint it.
+        #[ink(message)]
+        pub fn controls_token(&self, token: AccountId) -> bool {
+            let token: ink::contract_ref!(Erc20) = token.into();
+            token.get_owner() == self.env().account_id()
+        }
+
+        #[ink(message)]
+        pub fn open_vault(
+            &mut self,
+            owner: AccountId,
+            vault: u8,
+            ref_id: Option<[u8; 32]>,
+        ) -> Result<u8, ContractError> {
+            let caller = self.env().caller();
+
+            // a retried submission gets back the vault its reference already opened
+            if let Some(vault) = ref_id.and_then(|ref_id| self.vault_refs.get(ref_id)) {
+                return Ok(vault);
+            }
+
+            if self.vaults.contains(vault) {
+                return Err(ContractError::VaultAlreadyExists);
+            }
+            self.check_collateral_ratio()?;
+            self.check_min_basket()?;
+            let fee_shares = self.fee_shares()?;
+            // like imported ids, the last id is never handed out
+            let next_vault_id = self
+                .next_vault_id
+                .checked_add(1)
+                .ok_or(ContractError::Overflow)?;
+            // no balance can exceed the supply, so it bounds the credits below too
+            let total_supply = self
+                .total_supply
+                .checked_add(self.shares_per_vault)
+                .ok_or(ContractError::Overflow)?;
+
+            // record the vault before pulling any collateral (checks-effects-interactions)
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                let escrow_balance = self.balances.get(token).unwrap_or(0);
+                self.balances
+                    .insert(token, &(escrow_balance + self.required_balances[i]));
+            }
+
+            let vault = self.next_vault_id;
+            self.vaults.insert(vault, &owner);
+            self.next_vault_id = next_vault_id;
+            self.live_vault_count += 1;
+            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(owner).unwrap_or(0);
+            self.vaults_quantity_per_owner
+                .insert(owner, &(vaults_quantity_of_owner + 1));
+            let mut vaults_of_owner = self.get_vaults_of_owner(owner);
+            vaults_of_owner.push(vault);
+            self.vaults_of_owner.insert(owner, &vaults_of_owner);
+            self.vault_collateral.insert(vault, &self.required_balances);
+            // later changes to the cooldown do not move an open vault's unlock time
+            if self.min_hold_duration > 0 {
+                let unlocks_at = self.env().block_timestamp().saturating_add(self.min_hold_duration);
+                self.vault_unlocks_at.insert(vault, &unlocks_at);
+            }
+
+            // mint the protocol fee share to the fee recipient and the rest to the caller
+            if fee_shares > 0 {
+                let recipient_balance = self.balances.get(self.fee_recipient).unwrap_or(0);
+                self.balances
+                    .insert(self.fee_recipient, &(recipient_balance + fee_shares));
+                // the fee is paid in the escrow's own share token
+                self.emit_info_event(FeeCollected {
+                    token: self.env().account_id(),
+                    amount: fee_shares,
+                    collector: self.fee_recipient,
+                });
+            }
+            let caller_balance = self.balances.get(caller).unwrap_or(0);
+            self.balances
+                .insert(caller, &(caller_balance + (self.shares_per_vault - fee_shares)));
+            self.total_supply = total_supply;
+
+            if let Some(ref_id) = ref_id {
+                self.vault_refs.insert(ref_id, &vault);
+            }
+
+            self.emit_event(VaultOpened {
+                vault,
+                owner,
+                shares: self.shares_per_vault,
+                block: self.env().block_number(),
+            });
+
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                // pull the required amount of every basket token from the caller
+                let balance_before = self.escrow_token_balance(*token)?;
+                self.pull_collateral(*token, caller, self.required_balances[i])?;
+
+                // a fee-on-transfer token delivering less would leave the vault undercollateralized
+                let received = self
+                    .escrow_token_balance(*token)?
+                    .saturating_sub(balance_before);
+                if received < self.required_balances[i] {
+                    return Err(ContractError::TransferShortfall);
+                }
+            }
+            Ok(vault)
+        }
+
+        #[ink(message)]
+        pub fn close_vault(&mut self, vault: u8) -> Result<(), ContractError> {
+            let payouts = self.vault_payouts(vault);
+            self.settle_vault(vault, self.env().caller(), &payouts).map(|_| ())
+        }
+
+        /// Closes `vault` paying out only `token`, worth the whole basket at oracle
+        /// prices less `penalty_bps`.
+        ///
+        /// Whatever of `token` is paid beyond the vault's own share is taken from the
+        /// other live vaults, pro rata to how much of it they hold, and they receive
+        /// the rest of the closed vault's basket in exchange, so their collateral keeps
+        /// its value and the penalty accrues to them. Closing fails with
+        /// `InsufficientCollateral` when they hold too little of `token`; with no other
+        /// vault left, what the payout leaves of the basket goes to the fee recipient.
+        ///
+        /// `penalty_bps` must be at least the configured single-token penalty.
+        #[ink(message)]
+        pub fn close_vault_single(
+            &mut self,
+            vault: u8,
+            token: AccountId,
+            penalty_bps: u16,
+        ) -> Result<Balance, ContractError> {
+            let oracle = self.price_oracle.ok_or(ContractError::OracleNotSet)?;
+            if !self.vaults.contains(vault) {
+                return Err(ContractError::VaultNotFound);
+            }
+            let index = self
+                .required_tokens
+                .iter()
+                .position(|required| *required == token)
+                .ok_or(ContractError::UnsupportedToken)?;
+            if penalty_bps > MAX_BPS {
+                return Err(ContractError::InvalidPenalty);
+            }
+            if penalty_bps < self.single_token_penalty_bps {
+                return Err(ContractError::PenaltyTooLow);
+            }
+
+            let collateral = self.vault_payouts(vault);
+            if collateral.len() != self.required_tokens.len() {
+                return Err(ContractError::LengthMismatch);
+            }
+            let value = self.basket_value(&collateral)?;
+            let amount = mul_div(value, 1, self.oracle_price(oracle, token)?, false)
+                .and_then(|amount| {
+                    mul_div(amount, Balance::from(MAX_BPS - penalty_bps), Balance::from(MAX_BPS), false)
+                })
+                .ok_or(ContractError::Overflow)?;
+            let own = amount.min(collateral[index]);
+            let mut leftover = collateral;
+            leftover[index] -= own;
+            let mut payouts = ink::prelude::vec![0; self.required_tokens.len()];
+            payouts[index] = amount;
+
+            let (recipient, payouts) = self.release_vault(vault, self.env().caller(), &payouts)?;
+            let forfeited = self.rebalance_collateral(index, &leftover, amount - own)?;
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                if forfeited[i] > 0 {
+                    let escrow_balance = self.balances.get(token).unwrap_or(0);
+                    self.balances.insert(token, &(escrow_balance - forfeited[i]));
+                    self.emit_info_event(FeeCollected {
+                        token: *token,
+                        amount: forfeited[i],
+                        collector: self.fee_recipient,
+                    });
+                }
+            }
+
+            self.transfer_token_from(token, self.env().account_id(), recipient, payouts[index])?;
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                if forfeited[i] > 0 {
+                    self.transfer_token_from(
+                        *token,
+                        self.env().account_id(),
+                        self.fee_recipient,
+                        forfeited[i],
+                    )?;
+                }
+            }
+            Ok(payouts[index])
+        }
+
+        // hands `leftover` of a closed vault to the other live vaults and takes `drawn`
+        // of the token at `index` from them, both pro rata to their holdings of it;
+        // returns what could not be handed over because no vault holds the token
+        fn rebalance_collateral(
+            &mut self,
+            index: usize,
+            leftover: &[Balance],
+            drawn: Balance,
+        ) -> Result<Vec<Balance>, ContractError> {
+            let others: Vec<(u8, Vec<Balance>)> = (0..self.next_vault_id)
+                .filter(|vault| self.vaults.contains(vault))
+                .map(|vault| (vault, self.vault_payouts(vault)))
+                .collect();
+            if others
+                .iter()
+                .any(|(_, collateral)| collateral.len() != self.required_tokens.len())
+            {
+                return Err(ContractError::LengthMismatch);
+            }
+            let weights: Vec<Balance> = others.iter().map(|(_, collateral)| collateral[index]).collect();
+            let held = weights
+                .iter()
+                .try_fold(0, |held: Balance, weight| held.checked_add(*weight))
+                .ok_or(ContractError::Overflow)?;
+            if held < drawn {
+                return Err(ContractError::InsufficientCollateral);
+            }
+            if held == 0 {
+                return Ok(leftover.to_vec());
+            }
+
+            let taken = split_pro_rata(drawn, &weights).ok_or(ContractError::Overflow)?;
+            let mut handed = Vec::with_capacity(leftover.len());
+            for amount in leftover {
+                handed.push(split_pro_rata(*amount, &weights).ok_or(ContractError::Overflow)?);
+            }
+            for (k, (vault, mut collateral)) in others.into_iter().enumerate() {
+                collateral[index] -= taken[k];
+                for (amount, parts) in collateral.iter_mut().zip(&handed) {
+                    *amount = amount.checked_add(parts[k]).ok_or(ContractError::Overflow)?;
+                }
+                self.vault_collateral.insert(vault, &collateral);
+            }
+            Ok(ink::prelude::vec![0; leftover.len()])
+        }
+
+        #[ink(message)]
+        pub fn get_single_token_penalty_bps(&self) -> u16 {
+            self.single_token_penalty_bps
+        }
+
+        #[ink(message)]
+        pub fn set_single_token_penalty_bps(&mut self, penalty_bps: u16) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can set the single-token penalty
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if penalty_bps > MAX_BPS {
+                return Err(ContractError::InvalidPenalty);
+            }
+            self.emit_info_event(ConfigChanged {
+                field: SINGLE_PENALTY_FIELD,
+                old: Balance::from(self.single_token_penalty_bps),
+                new: Balance::from(penalty_bps),
+            });
+            self.single_token_penalty_bps = penalty_bps;
+            Ok(())
+        }
+
+        /// Closes `vault` with `from`'s shares, for `from` or an operator they approved.
+        #[ink(message)]
+        pub fn close_vault_from(&mut self, from: AccountId, vault: u8) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            if caller != from && !self.is_operator(from, caller) {
+                return Err(ContractError::NotAuthorized);
+            }
+            let payouts = self.vault_payouts(vault);
+            self.settle_vault(vault, from, &payouts).map(|_| ())
+        }
+
+        /// Closes `vault` on behalf of `shares_source`, burning their shares and
+        /// paying them the collateral. Unlike a liquidation the shares must exist,
+        /// and `shares_source` must have approved the admin for them.
+        #[ink(message)]
+        pub fn admin_close_vault(
+            &mut self,
+            vault: u8,
+            shares_source: AccountId,
+        ) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can close a vault on someone's behalf
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if self.free_shares(shares_source) < self.shares_per_vault {
+                return Err(ContractError::InsufficientBalance);
+            }
+            self.spend_allowance(shares_source, caller, self.shares_per_vault)?;
+            let payouts = self.vault_payouts(vault);
+            self.settle_vault(vault, shares_source, &payouts).map(|_| ())
+        }
+
+        /// Closes `vault` reading its recorded collateral as amounts with
+        /// `NORMALIZED_DECIMALS` and paying each token out in its own `decimals`,
+        /// so tokens of different precision are redeemed at equal weight.
+        /// Whatever a rescaled payout leaves behind stays in the collateral.
+        #[ink(message)]
+        pub fn close_vault_normalized(
+            &mut self,
+            vault: u8,
+            decimals: Vec<u8>,
+        ) -> Result<Vec<(AccountId, Balance)>, ContractError> {
+            if decimals.len() != self.required_tokens.len() {
+                return Err(ContractError::LengthMismatch);
+            }
+            let mut payouts = Vec::new();
+            for (amount, decimals) in self.vault_payouts(vault).into_iter().zip(decimals) {
+                payouts.push(
+                    normalize_amount(amount, NORMALIZED_DECIMALS, decimals)
+                        .ok_or(ContractError::Overflow)?,
+                );
+            }
+            let payouts = self.settle_vault(vault, self.env().caller(), &payouts)?;
+            Ok(self.required_tokens.iter().copied().zip(payouts).collect())
+        }
+
+        // burns a vault's worth of `holder`'s shares and pays `payouts[i]` of each required
+        // token less the redemption fee; returns what was actually paid out
+        fn settle_vault(
+            &mut self,
+            vault: u8,
+            holder: AccountId,
+            payouts: &[Balance],
+        ) -> Result<Vec<Balance>, ContractError> {
+            let (recipient, payouts) = self.release_vault(vault, holder, payouts)?;
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                self.transfer_token_from(*token, self.env().account_id(), recipient, payouts[i])?;
+            }
+            Ok(payouts)
+        }
+
+        // checks `holder` may close `vault` paying out `payouts`; returns the vault's owner
+        fn ensure_closable(
+            &self,
+            vault: u8,
+            holder: AccountId,
+            payouts: &[Balance],
+        ) -> Result<AccountId, ContractError> {
+            let owner = self.vaults.get(vault).ok_or(ContractError::VaultNotFound)?;
+            // a snapshot recorded for another basket cannot be matched to the current tokens
+            if payouts.len() != self.required_tokens.len() {
+                return Err(ContractError::LengthMismatch);
+            }
+            // deters opening and closing a vault within the same flash loan
+            let now = self.env().block_timestamp();
+            if self.vault_unlocks_at(vault).is_some_and(|unlocks_at| now < unlocks_at) {
+                return Err(ContractError::CooldownActive);
+            }
+
+            // check the holder has enough shares to close the vault and reedem the tokens
+            if self.free_shares(holder) < self.shares_per_vault {
+                return Err(ContractError::InsufficientBalance);
+            }
+
+            // check every payout is covered before moving anything, so a drifted
+            // collateral entry cannot leave the vault half redeemed
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                if self.balances.get(token).unwrap_or(0) < payouts[i] {
+                    return Err(ContractError::InsufficientCollateral);
+                }
+            }
+            Ok(owner)
+        }
+
+        // settles the vault and collects its fees, leaving the payouts net of fees in the
+        // escrow; returns who they belong to alongside them
+        fn release_vault(
+            &mut self,
+            vault: u8,
+            holder: AccountId,
+            payouts: &[Balance],
+        ) -> Result<(AccountId, Vec<Balance>), ContractError> {
+            let owner = self.ensure_closable(vault, holder, payouts)?;
+            let holder_shares_balance = self.balances.get(holder).unwrap_or(0);
+            // larger redemptions land in cheaper tiers
+            let fee_bps = if self.fee_tiers.is_empty() {
+                0
+            } else {
+                self.redemption_fee_bps(self.basket_value(payouts)?)
+            };
+            let mut fees = Vec::with_capacity(payouts.len());
+            for payout in payouts.iter() {
+                fees.push(
+                    mul_div(*payout, Balance::from(fee_bps), Balance::from(MAX_BPS), false)
+                        .ok_or(ContractError::Overflow)?,
+                );
+            }
+
+            // settle the vault before paying anything out (checks-effects-interactions)
+            let _ = self
+                .balances
+                .insert(holder, &(holder_shares_balance - self.shares_per_vault));
+            self.total_supply -= self.shares_per_vault;
+
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                let escrow_balance = self.balances.get(token).unwrap_or(0);
+                self.balances
+                    .insert(token, &(escrow_balance - payouts[i]));
+            }
+
+            self.vaults.remove(vault);
+            self.live_vault_count -= 1;
+            self.vault_unlocks_at.remove(vault);
+            self.vault_collateral.remove(vault);
+            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(owner).unwrap_or(0);
+            self.vaults_quantity_per_owner
+                .insert(owner, &(vaults_quantity_of_owner - 1));
+            let mut vaults_of_owner = self.get_vaults_of_owner(owner);
+            vaults_of_owner.retain(|id| *id != vault);
+            self.vaults_of_owner.insert(owner, &vaults_of_owner);
+            self.emit_event(VaultClosed {
+                vault,
+                owner,
+                redeemer: holder,
+                shares_burned: self.shares_per_vault,
+            });
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                if fees[i] > 0 {
+                    self.emit_info_event(FeeCollected {
+                        token: *token,
+                        amount: fees[i],
+                        collector: self.fee_recipient,
+                    });
+                }
+            }
+
+            // the redemption fee is collected before any collateral is released
+            if let Some(fee_token) = self.fee_token {
+                if self.fee_amount > 0 {
+                    self.transfer_token_from(
+                        fee_token,
+                        holder,
+                        self.fee_recipient,
+                        self.fee_amount,
+                    )?;
+                }
+            }
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                if fees[i] > 0 {
+                    self.transfer_token_from(
+                        *token,
+                        self.env().account_id(),
+                        self.fee_recipient,
+                        fees[i],
+                    )?;
+                }
+            }
+            // the shares are always burnt from the holder, the collateral may go back to the owner
+            let recipient = if self.redeem_to_owner { owner } else { holder };
+            let payouts = payouts.iter().zip(fees).map(|(payout, fee)| payout - fee).collect();
+            Ok((recipient, payouts))
+        }
+
+        /// Closes `vault` and swaps its whole basket into `out_token` through `router`,
+        /// reverting unless at least `min_out` is paid out.
+        ///
+        /// Each basket token is sent to the router before asking it to `swap`. What
+        /// the escrow actually receives is measured, so a router cannot overstate it.
+        #[ink(message)]
+        pub fn close_vault_to_token(
+            &mut self,
+            vault: u8,
+            out_token: AccountId,
+            min_out: Balance,
+            router: AccountId,
+        ) -> Result<Balance, ContractError> {
+            if !self.vaults.contains(vault) {
+                return Err(ContractError::VaultNotFound);
+            }
+            let payouts = self.vault_payouts(vault);
+            let (recipient, payouts) = self.release_vault(vault, self.env().caller(), &payouts)?;
+
+            let escrow = self.env().account_id();
+            let before = self.escrow_token_balance(out_token)?;
+            let mut kept = 0;
+            for (i, token) in self.required_tokens.iter().enumerate() {
+                // a basket token asked for as the output needs no swap
+                if *token == out_token {
+                    kept = payouts[i];
+                    continue;
+                }
+                if payouts[i] == 0 {
+                    continue;
+                }
+                self.transfer_token_from(*token, escrow, router, payouts[i])?;
+                self.invoke_token::<_, Result<Balance, ContractError>>(
+                    router,
+                    ExecutionInput::new(Selector::new(SWAP_SELECTOR))
+                        .push_arg(*token)
+                        .push_arg(payouts[i])
+                        .push_arg(out_token)
+                        .push_arg(escrow),
+                )
+                .ok()
+                .and_then(|result| result.ok())
+                .ok_or(ContractError::SwapFailed)?;
+            }
+            let swapped = self.escrow_token_balance(out_token)?.saturating_sub(before);
+            let amount_out = swapped.checked_add(kept).ok_or(ContractError::Overflow)?;
+            if amount_out < min_out {
+                return Err(ContractError::SlippageExceeded);
+            }
+            self.transfer_token_from(out_token, escrow, recipient, amount_out)?;
+            Ok(amount_out)
+        }
+
+        #[ink(message)]
+        pub fn close_all_vaults(&mut self) -> Result<Vec<u8>, ContractError> {
+            let caller = self.env().caller();
+            let vaults = self.get_vaults_of_owner(caller);
+            let count = vaults.len() as Balance;
+
+            // check the whole batch upfront so it is closed entirely or not at all
+            let shares_needed = self
+                .shares_per_vault
+                .checked_mul(count)
+                .ok_or(ContractError::Overflow)?;
+            if self.free_shares(caller) < shares_needed {
+                return Err(ContractError::InsufficientBalance);
+            }
+            let totals = self.batch_payouts(&vaults)?;
+            for (token, total) in self.required_tokens.iter().zip(totals) {
+                if self.balances.get(token).unwrap_or(0) < total {
+                    return Err(ContractError::InsufficientCollateral);
+                }
+            }
+
+            for vault in vaults.iter() {
+                self.close_vault(*vault)?;
+            }
+            Ok(vaults)
+        }
+
+        #[ink(message)]
+        pub fn redeem(&mut self, vault: u8) -> Result<Vec<(AccountId, Balance)>, ContractError> {
+            // closing a vault pays out the basket it was opened with, less fees
+            let payouts = self.vault_payouts(vault);
+            let payouts = self.settle_vault(vault, self.env().caller(), &payouts)?;
+            Ok(self.required_tokens.iter().copied().zip(payouts).collect())
+        }
+
+        #[ink(message)]
+        pub fn redeem_shares(
+            &mut self,
+            vault: u8,
+            shares: Balance,
+        ) -> Result<Vec<(AccountId, Balance)>, ContractError> {
+            // vaults are redeemed whole so none is ever left with dust backing
+            if shares != self.shares_per_vault {
+                return Err(ContractError::PartialRedeemNotAllowed);
+            }
+            self.redeem(vault)
+        }
+
+        #[ink(message)]
+        pub fn rescue_shares(&mut self, to: AccountId, amount: Balance) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can rescue shares
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+
+            // shares sent to the escrow itself are stuck, move them out without minting new ones
+            let escrow = self.env().account_id();
+            let escrow_shares_balance = self.balances.get(escrow).unwrap_or(0);
+            if escrow_shares_balance < amount {
+                return Err(ContractError::InsufficientBalance);
+            }
+
+            self.balances.insert(escrow, &(escrow_shares_balance - amount));
+            let to_balance = self.balances.get(to).unwrap_or(0);
+            self.balances.insert(to, &(to_balance + amount));
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn burn_own_shares(&mut self, amount: Balance) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can burn the escrow's shares
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+
+            // shares held by the escrow itself back nobody, so they can be retired
+            let escrow = self.env().account_id();
+            let escrow_shares_balance = self.balances.get(escrow).unwrap_or(0);
+            if escrow_shares_balance < amount {
+                return Err(ContractError::InsufficientBalance);
+            }
+
+            self.balances.insert(escrow, &(escrow_shares_balance - amount));
+            self.total_supply -= amount;
+            Ok(())
+        }
+
+        /// Moves `amount` of a basket token out regardless of the internal accounting,
+        /// then resets the recorded collateral to what the escrow actually holds.
+        ///
+        /// Meant only for recovering from corrupt accounting.
+        #[ink(message)]
+        pub fn emergency_withdraw(
+            &mut self,
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<Balance, ContractError> {
+            let caller = self.env().caller();
+            // only the admin can bypass the accounting
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            // shares live in the same mapping, so only basket tokens may be resynced
+            if !self.required_tokens.contains(&token) {
+                return Err(ContractError::UnsupportedToken);
+            }
+            Self::ensure_non_zero(to)?;
+
+            self.invoke_token::<_, Result<Balance, ContractError>>(
+                token,
+                ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                    .push_arg(to)
+                    .push_arg(amount),
+            )
+            .ok()
+            .and_then(|result| result.ok())
+            .ok_or(ContractError::TokenTransferFailed { token })?;
+
+            // the new collateral can only be known once the transfer went through
+            let collateral = self.escrow_token_balance(token)?;
+            self.balances.insert(token, &collateral);
+            self.emit_event(EmergencyWithdraw { token, to, amount, collateral });
+            Ok(collateral)
+        }
+
+        #[ink(message)]
+        pub fn absorb_surplus(&mut self, token: AccountId) -> Result<Balance, ContractError> {
+            let caller = self.env().caller();
+            // only the admin can absorb surplus collateral
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if !self.required_tokens.contains(&token) {
+                return Err(ContractError::UnsupportedToken);
+            }
+
+            // tokens sent directly to the escrow are credited to the collateral they back
+            let actual = self.escrow_token_balance(token)?;
+            let internal = self.balances.get(token).unwrap_or(0);
+            let surplus = actual.saturating_sub(internal);
+            self.balances.insert(token, &(internal + surplus));
+            Ok(surplus)
+        }
+
+        /// Overwrites the internal collateral of `token` with the escrow's actual
+        /// balance, to recover once `reconcile` has shown them drifting apart.
+        #[ink(message)]
+        pub fn resync_collateral(&mut self, token: AccountId) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            // only the admin can resync the collateral
+            if caller != self.admin {
+                return Err(ContractError::NotAuthorized);
+            }
+            if !self.required_tokens.contains(&token) {
+                return Err(ContractError::UnsupportedToken);
+            }
+
+            let new = self.escrow_token_balance(token)?;
+            let old = self.balances.get(token).unwrap_or(0);
+            self.balances.insert(token, &new);
+            self.emit_info_event(CollateralResynced { token, old, new });
+            Ok(())
+        }
+    }
+
+    impl Erc20 for EtfEscrow {
+
+        #[ink(message)]
+        fn get_name(&self) -> String {
+            self.share_name.clone()
+        }
+
+        #[ink(message)]
+        fn get_symbol(&self) -> String {
+            self.share_symbol.clone()
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(owner).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.total_supply
+        }
+
+        #[ink(message)]
+        fn get_owner(&self) -> AccountId {
+            self.admin
+        }
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, value: Balance) -> Result<Balance, ContractError> {
+            Self::ensure_non_zero(to)?;
+            let from = self.env().caller();
+            self.ensure_allowed(from, to)?;
+            let from_balance = self.balance_of(from);
+            if self.free_shares(from) < value {
+                return Err(ContractError::InsufficientBalance);
+            }
+            let to_balance = self.balance_of(to);
+
+            self.balances.insert(from, &(from_balance - value));
+            self.balances.insert(to, &(to_balance + value));
+
+            Ok(self.balance_of(from))
+        }
+
+        #[ink(message, selector = 6)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<Balance, ContractError> {
+            Self::ensure_non_zero(to)?;
+            self.ensure_allowed(from, to)?;
+            let caller = self.env().caller();
+            let from_balance = self.balance_of(from);
+            if self.free_shares(from) < value {
+                return Err(ContractError::InsufficientBalance);
+            }
+            self.spend_allowance(from, caller, value)?;
+            let to_balance = self.balance_of(to);
+
+            self.balances.insert(from, &(from_balance - value));
+            self.balances.insert(to, &(to_balance + value));
 
             Ok(self.balance_of(from))
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::mock_erc20;
+
+        type Event = <EtfEscrow as ink::reflect::ContractEventBase>::Type;
+
+        // constructor arguments after the basket; the defaults are the ones `new_etf` uses
+        struct EtfConfig {
+            initial_treasury: Option<(AccountId, Balance)>,
+            protocol_fee_bps: u16,
+            fee_recipient: AccountId,
+            share_name: String,
+            share_symbol: String,
+            share_decimals: u8,
+            enforce_allowlist: bool,
+            redeem_to_owner: bool,
+            fee_tiers: Vec<(Balance, u16)>,
+            emit_events: bool,
+        }
+
+        impl Default for EtfConfig {
+            fn default() -> Self {
+                let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+                Self {
+                    initial_treasury: None,
+                    protocol_fee_bps: 0,
+                    fee_recipient: accounts.alice,
+                    share_name: "XTF-Index-0".to_string(),
+                    share_symbol: "XTF".to_string(),
+                    share_decimals: 0,
+                    enforce_allowlist: false,
+                    redeem_to_owner: false,
+                    fee_tiers: Vec::new(),
+                    emit_events: true,
+                }
+            }
+        }
+
+        fn new_etf(required_tokens: Vec<AccountId>, required_balances: Vec<Balance>) -> EtfEscrow {
+            new_etf_with(required_tokens, required_balances, EtfConfig::default())
+        }
+
+        fn new_etf_with(
+            required_tokens: Vec<AccountId>,
+            required_balances: Vec<Balance>,
+            config: EtfConfig,
+        ) -> EtfEscrow {
+            EtfEscrow::new(
+                required_tokens,
+                required_balances,
+                config.initial_treasury,
+                config.protocol_fee_bps,
+                config.fee_recipient,
+                config.share_name,
+                config.share_symbol,
+                config.share_decimals,
+                config.enforce_allowlist,
+                config.redeem_to_owner,
+                config.fee_tiers,
+                config.emit_events,
+            )
+        }
+
+        #[ink::test]
+        fn rescue_shares_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            // an empty basket lets vaults be opened without cross-contract calls
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(etf.transfer(accounts.django, 40), Ok(SHARES - 40));
+            assert_eq!(
+                etf.rescue_shares(accounts.bob, 41),
+                Err(ContractError::InsufficientBalance)
+            );
+            assert_eq!(etf.rescue_shares(accounts.bob, 40), Ok(()));
+            assert_eq!(etf.balance_of(accounts.django), 0);
+            assert_eq!(etf.balance_of(accounts.bob), 40);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.rescue_shares(accounts.bob, 0),
+                Err(ContractError::NotAuthorized)
+            );
+        }
+
+        #[ink::test]
+        fn initial_treasury_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    initial_treasury: Some((accounts.bob, 500)),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.balance_of(accounts.bob), 500);
+            assert_eq!(etf.total_supply(), 500);
+
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.total_supply(), 500 + SHARES);
+        }
+
+        #[ink::test]
+        fn treasury_cannot_redeem_its_seed() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf_with(
+                vec![token],
+                vec![10],
+                EtfConfig {
+                    initial_treasury: Some((accounts.bob, 2 * SHARES)),
+                    ..Default::default()
+                },
+            );
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // the seeded shares back no vault, so they cannot close alice's or leave bob
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.close_vault(vault), Err(ContractError::InsufficientBalance));
+            assert_eq!(etf.transfer(accounts.charlie, SHARES), Err(ContractError::InsufficientBalance));
+            assert_eq!(etf.redeemable_vaults(accounts.bob), Ok((0, vec![(token, 0)])));
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 0);
+
+            // shares bob receives on top of the seed are his to redeem
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            etf.transfer(accounts.bob, SHARES).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.close_vault(vault), Ok(()));
+            assert_eq!(etf.balance_of(accounts.bob), 2 * SHARES);
+        }
+
+        #[ink::test]
+        fn open_vault_rejects_overflowing_supply() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    initial_treasury: Some((accounts.bob, Balance::MAX)),
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Err(ContractError::Overflow));
+            assert_eq!(etf.total_supply(), Balance::MAX);
+        }
+
+        #[ink::test]
+        fn stats_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.bob, 1, None).unwrap();
+
+            assert_eq!(
+                etf.stats(),
+                Stats {
+                    vaults_quantity: 2,
+                    total_supply: 2 * SHARES,
+                    required_token_count: 0,
+                }
+            );
+
+            let basket = new_etf(vec![accounts.eve, accounts.frank], vec![10, 20]);
+            assert_eq!(basket.stats().required_token_count, 2);
+        }
+
+        #[ink::test]
+        fn open_vault_pulls_basket() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+
+            mock_erc20::mint(first, accounts.alice, 100);
+            mock_erc20::mint(second, accounts.alice, 100);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 10);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 20);
+
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(etf.get_balance(first), 10);
+            assert_eq!(etf.get_balance(second), 20);
+            assert_eq!(mock_erc20::balance_of(first, accounts.django), 10);
+            assert_eq!(mock_erc20::balance_of(second, accounts.django), 20);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 80);
+
+            // the allowance is used up, so a second vault cannot be funded
+            assert_eq!(
+                etf.open_vault(accounts.alice, 1, None),
+                Err(ContractError::TokenTransferFailed { token: first })
+            );
+        }
+
+        #[ink::test]
+        fn open_vault_fails_when_token_rejects() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 100);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            mock_erc20::reject_transfers(token);
+
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::TokenTransferFailed { token })
+            );
+            // the bookkeeping is written before the call, so only the error reverting
+            // it on-chain undoes it; off-chain just check that no tokens moved
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 100);
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 0);
+        }
+
+        #[ink::test]
+        fn redeem_returns_basket() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            mock_erc20::mint(first, accounts.alice, 10);
+            mock_erc20::mint(second, accounts.alice, 20);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 10);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 20);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(etf.redeem(vault), Ok(vec![(first, 10), (second, 20)]));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 10);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 20);
+            assert_eq!(etf.get_balance(first), 0);
+            assert_eq!(etf.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn redeem_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+            let other_vault = etf.open_vault(accounts.alice, 1, None).unwrap();
+
+            assert_eq!(etf.redeem(vault), Ok(Vec::new()));
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(etf.get_vaults_quantity_per_owner(accounts.alice), 1);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.redeem(other_vault), Err(ContractError::InsufficientBalance));
+        }
+
+        #[ink::test]
+        fn protocol_fee_split_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    protocol_fee_bps: 500,
+                    fee_recipient: accounts.charlie,
+                    ..Default::default()
+                },
+            );
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(etf.balance_of(accounts.charlie), 5);
+            assert_eq!(etf.balance_of(accounts.alice), 95);
+            assert_eq!(
+                etf.balance_of(accounts.charlie) + etf.balance_of(accounts.alice),
+                SHARES
+            );
+            assert_eq!(etf.total_supply(), SHARES);
+        }
+
+        #[ink::test]
+        fn version_works() {
+            let etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(etf.version(), 1);
+        }
+
+        #[ink::test]
+        fn mul_div_rounds_at_boundaries() {
+            assert_eq!(mul_div(100, 500, 10_000, false), Some(5));
+            assert_eq!(mul_div(100, 500, 10_000, true), Some(5));
+            assert_eq!(mul_div(99, 500, 10_000, false), Some(4));
+            assert_eq!(mul_div(99, 500, 10_000, true), Some(5));
+            assert_eq!(mul_div(1, 1, 10_000, false), Some(0));
+            assert_eq!(mul_div(1, 1, 10_000, true), Some(1));
+            assert_eq!(mul_div(0, 1, 10_000, true), Some(0));
+            assert_eq!(mul_div(1, 1, 0, false), None);
+            assert_eq!(mul_div(Balance::MAX, 2, 1, false), None);
+        }
+
+        #[ink::test]
+        fn close_vault_requires_collateral() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            mock_erc20::mint(first, accounts.alice, 10);
+            mock_erc20::mint(second, accounts.alice, 20);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 10);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 20);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // simulate an accounting error on the second token
+            etf.balances.insert(second, &15);
+
+            assert_eq!(etf.close_vault(vault), Err(ContractError::InsufficientCollateral));
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 0);
+            assert_eq!(etf.get_balance(first), 10);
+        }
+
+        #[ink::test]
+        fn token_labels_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            assert_eq!(etf.get_token_labels(), Vec::new());
+
+            assert_eq!(etf.set_token_label(second, "DOT".to_string()), Ok(()));
+            assert_eq!(etf.set_token_label(first, "AZERO".to_string()), Ok(()));
+            assert_eq!(
+                etf.get_token_labels(),
+                vec![(first, "AZERO".to_string()), (second, "DOT".to_string())]
+            );
+            assert_eq!(
+                etf.set_token_label(accounts.eve, "EVE".to_string()),
+                Err(ContractError::UnsupportedToken)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.set_token_label(first, "BOB".to_string()),
+                Err(ContractError::NotAuthorized)
+            );
+        }
+
+        #[ink::test]
+        fn open_vault_is_idempotent_per_ref() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 30);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 30);
+
+            let vault = etf.open_vault(accounts.alice, 0, Some([7; 32])).unwrap();
+            assert_eq!(etf.open_vault(accounts.alice, 0, Some([7; 32])), Ok(vault));
+            assert_eq!(etf.get_vaults_quantity(), 1);
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 20);
+
+            // a different reference opens a new vault
+            assert_eq!(etf.open_vault(accounts.alice, 1, Some([8; 32])), Ok(vault + 1));
+            assert_eq!(etf.get_vaults_quantity(), 2);
+        }
+
+        #[ink::test]
+        fn reconcile_reports_drift() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.reconcile(), vec![(token, 10, 10)]);
+
+            // tokens sent straight to the escrow are not accounted as collateral
+            mock_erc20::mint(token, accounts.django, 5);
+            assert_eq!(etf.reconcile(), vec![(token, 10, 15)]);
+        }
+
+        #[ink::test]
+        fn absorb_surplus_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.absorb_surplus(token), Ok(0));
+
+            mock_erc20::mint(token, accounts.django, 5);
+            assert_eq!(etf.absorb_surplus(accounts.eve), Err(ContractError::UnsupportedToken));
+            assert_eq!(etf.absorb_surplus(token), Ok(5));
+            assert_eq!(etf.get_balance(token), 15);
+            assert_eq!(etf.reconcile(), vec![(token, 15, 15)]);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.absorb_surplus(token), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn fee_collected_event_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    protocol_fee_bps: 500,
+                    fee_recipient: accounts.charlie,
+                    ..Default::default()
+                },
+            );
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // FeeCollected is emitted before VaultOpened
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            let event = <Event as scale::Decode>::decode(&mut &events[0].data[..])
+                .expect("invalid event data");
+            match event {
+                Event::FeeCollected(FeeCollected { token, amount, collector }) => {
+                    assert_eq!(token, accounts.django);
+                    assert_eq!(amount, 5);
+                    assert_eq!(collector, accounts.charlie);
+                }
+                _ => panic!("expected FeeCollected"),
+            }
+            // signature topic plus the token and collector topics
+            assert_eq!(events[0].topics.len(), 3);
+            for (topic, prefix, value) in [
+                (&events[0].topics[1], &b"EtfEscrow::FeeCollected::token"[..], accounts.django),
+                (&events[0].topics[2], &b"EtfEscrow::FeeCollected::collector"[..], accounts.charlie),
+            ] {
+                // topics are the blake2 hash of the prefixed, encoded field
+                let mut expected = [0u8; 32];
+                ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(&(prefix, value), &mut expected);
+                assert_eq!(topic[..], expected[..]);
+            }
+        }
+
+        #[ink::test]
+        fn close_all_vaults_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            for vault in 0..3 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+            etf.open_vault(accounts.bob, 3, None).unwrap();
+            assert_eq!(etf.get_vaults_of_owner(accounts.alice), vec![0, 1, 2]);
+
+            assert_eq!(etf.close_all_vaults(), Ok(vec![0, 1, 2]));
+            assert_eq!(etf.get_vaults_of_owner(accounts.alice), Vec::<u8>::new());
+            assert_eq!(etf.get_vaults_quantity_per_owner(accounts.alice), 0);
+            assert_eq!(etf.get_vaults_of_owner(accounts.bob), vec![3]);
+            // alice paid for bob's vault too, so one vault's worth of shares is left
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(etf.total_supply(), SHARES);
+        }
+
+        #[ink::test]
+        fn close_all_vaults_requires_shares_for_every_vault() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            for vault in 0..3 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+            etf.transfer(accounts.bob, SHARES).unwrap();
+
+            assert_eq!(etf.close_all_vaults(), Err(ContractError::InsufficientBalance));
+            assert_eq!(etf.get_vaults_of_owner(accounts.alice), vec![0, 1, 2]);
+            assert_eq!(etf.balance_of(accounts.alice), 2 * SHARES);
+            assert_eq!(etf.total_supply(), 3 * SHARES);
+        }
+
+        #[ink::test]
+        fn preview_open_matches_open_vault() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf_with(
+                vec![first, second],
+                vec![10, 20],
+                EtfConfig {
+                    protocol_fee_bps: 500,
+                    fee_recipient: accounts.charlie,
+                    ..Default::default()
+                },
+            );
+            mock_erc20::mint(first, accounts.alice, 20);
+            mock_erc20::mint(second, accounts.alice, 40);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 20);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 40);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            let (vault, shares, collateral) = etf.preview_open(accounts.alice);
+            assert_eq!(vault, 1);
+            assert_eq!(shares, 95);
+            assert_eq!(collateral, vec![(first, 10), (second, 20)]);
+
+            let balance_before = etf.balance_of(accounts.alice);
+            assert_eq!(etf.open_vault(accounts.alice, 1, None), Ok(vault));
+            assert_eq!(etf.balance_of(accounts.alice) - balance_before, shares);
+            for (token, amount) in collateral {
+                assert_eq!(mock_erc20::balance_of(token, accounts.django), 2 * amount);
+            }
+        }
+
+        #[ink::test]
+        fn fractional_shares_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    share_decimals: 6,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.share_decimals(), 6);
+            assert_eq!(etf.shares_per_vault(), SHARES * 1_000_000);
+
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            // half a whole share
+            assert_eq!(etf.transfer(accounts.bob, 500_000), Ok(SHARES * 1_000_000 - 500_000));
+            assert_eq!(etf.balance_of(accounts.bob), 500_000);
+            assert_eq!(etf.total_supply(), etf.shares_per_vault());
+            assert_eq!(etf.close_vault(0), Err(ContractError::InsufficientBalance));
+        }
+
+        #[ink::test]
+        fn nav_in_reference_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            mock_erc20::mint(first, accounts.alice, 10);
+            mock_erc20::mint(second, accounts.alice, 20);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 10);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 20);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.nav_in_reference(), Err(ContractError::OracleNotSet));
+
+            mock_erc20::set_price(oracle, first, 3);
+            mock_erc20::set_price(oracle, second, 7);
+            assert_eq!(etf.set_price_oracle(Some(oracle)), Ok(()));
+            assert_eq!(etf.get_price_oracle(), Some(oracle));
+            assert_eq!(etf.nav_in_reference(), Ok(10 * 3 + 20 * 7));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_price_oracle(None), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn open_vault_rejects_transfer_shortfall() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            mock_erc20::set_transfer_fee(token, 1);
+
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::TransferShortfall)
+            );
+            // on-chain the error also reverts the transfer and the bookkeeping before it
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 9);
+        }
+
+        #[ink::test]
+        fn set_shares_per_vault_works_before_launch() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(etf.set_shares_per_vault(250), Ok(()));
+            assert_eq!(etf.shares_per_vault(), 250);
+
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.balance_of(accounts.alice), 250);
+            assert_eq!(etf.total_supply(), 250);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_shares_per_vault(10), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn set_shares_per_vault_rejects_open_vaults() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(etf.set_shares_per_vault(250), Err(ContractError::VaultsStillOpen));
+            assert_eq!(etf.shares_per_vault(), SHARES);
+        }
+
+        #[ink::test]
+        fn set_shares_per_vault_rejects_fractional_shares() {
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    share_decimals: 2,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.set_shares_per_vault(0), Err(ContractError::InvalidSharesPerVault));
+            assert_eq!(etf.set_shares_per_vault(150), Err(ContractError::InvalidSharesPerVault));
+            assert_eq!(etf.shares_per_vault(), SHARES * 100);
+
+            assert_eq!(etf.set_shares_per_vault(300), Ok(()));
+            assert_eq!(etf.shares_per_vault(), 300);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "required balance must be non-zero")]
+        fn new_rejects_zero_required_balance() {
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            new_etf(vec![first, second], vec![10, 0]);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "basket cannot contain the escrow itself")]
+        fn new_rejects_self_referential_basket() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            new_etf(vec![token, accounts.django], vec![10, 10]);
+        }
+
+        #[ink::test]
+        fn partial_redeem_is_rejected() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(
+                etf.redeem_shares(vault, SHARES / 2),
+                Err(ContractError::PartialRedeemNotAllowed)
+            );
+            assert_eq!(
+                etf.redeem_shares(vault, SHARES + 1),
+                Err(ContractError::PartialRedeemNotAllowed)
+            );
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(etf.get_vaults_of_owner(accounts.alice), vec![vault]);
+
+            assert_eq!(etf.redeem_shares(vault, SHARES), Ok(Vec::new()));
+            assert_eq!(etf.balance_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn vault_opened_event_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            let vault = etf.open_vault(accounts.bob, 0, None).unwrap();
+
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 1);
+            let event = <Event as scale::Decode>::decode(&mut &events[0].data[..])
+                .expect("invalid event data");
+            match event {
+                Event::VaultOpened(opened) => {
+                    assert_eq!(opened.vault, vault);
+                    assert_eq!(opened.owner, accounts.bob);
+                    assert_eq!(opened.shares, SHARES);
+                    assert_eq!(opened.block, 1);
+                }
+                _ => panic!("expected VaultOpened"),
+            }
+            // signature topic plus the vault and owner topics
+            assert_eq!(events[0].topics.len(), 3);
+        }
+
+        #[ink::test]
+        fn required_token_paging_works() {
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let etf = new_etf(vec![first, second], vec![10, 20]);
+            assert_eq!(etf.required_tokens_count(), 2);
+            assert_eq!(etf.required_token_at(0), Some(first));
+            assert_eq!(etf.required_token_at(1), Some(second));
+            assert_eq!(etf.required_token_at(2), None);
+
+            let empty = new_etf(Vec::new(), Vec::new());
+            assert_eq!(empty.required_tokens_count(), 0);
+            assert_eq!(empty.required_token_at(0), None);
+        }
+
+        #[ink::test]
+        fn get_vault_owners_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.bob, 1, None).unwrap();
+            etf.close_vault(0).unwrap();
+
+            assert_eq!(
+                etf.get_vault_owners(vec![1, 0, 7, 1]),
+                vec![Some(accounts.bob), None, None, Some(accounts.bob)]
+            );
+            assert_eq!(etf.get_vault_owners(Vec::new()), Vec::new());
+        }
+
+        #[ink::test]
+        fn open_vault_rejects_exhausted_ids() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(etf.import_vaults(vec![(254, accounts.bob)]), Ok(()));
+            assert_eq!(etf.get_next_vault_id(), 255);
+
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Err(ContractError::Overflow));
+            assert_eq!(etf.get_next_vault_id(), 255);
+            assert_eq!(etf.balance_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn unknown_vault_is_not_found() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.get_vault_owner(0), Ok(accounts.alice));
+
+            assert_eq!(etf.get_vault_owner(7), Err(ContractError::VaultNotFound));
+            assert_eq!(etf.close_vault(7), Err(ContractError::VaultNotFound));
+            assert_eq!(etf.close_vault_from(accounts.alice, 7), Err(ContractError::VaultNotFound));
+            assert_eq!(etf.admin_close_vault(7, accounts.alice), Err(ContractError::VaultNotFound));
+            assert_eq!(
+                etf.close_vault_normalized(7, Vec::new()),
+                Err(ContractError::VaultNotFound)
+            );
+            assert_eq!(etf.redeem(7), Err(ContractError::VaultNotFound));
+            // nothing was burnt for the missing vault
+            assert_eq!(etf.balance_of(accounts.alice), SHARES);
+            assert_eq!(etf.get_vaults_quantity(), 1);
+        }
+
+        #[ink::test]
+        fn open_vault_enforces_collateral_ratio() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 20);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+            mock_erc20::set_price(oracle, token, 12);
+            etf.set_price_oracle(Some(oracle)).unwrap();
+
+            // 10 tokens at 12 back 120 of value, short of 150% of the 100 shares
+            assert_eq!(etf.set_min_collateral_ratio_bps(15_000), Ok(()));
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::UndercollateralizedOpen)
+            );
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 20);
+
+            // but enough for 120%
+            assert_eq!(etf.set_min_collateral_ratio_bps(12_000), Ok(()));
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(etf.get_min_collateral_ratio_bps(), 12_000);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_min_collateral_ratio_bps(0), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn open_vault_names_failing_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            mock_erc20::reject_transfers(second);
+
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::TokenTransferFailed { token: second })
+            );
+        }
+
+        #[ink::test]
+        fn can_close_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert!(etf.can_close(accounts.alice, 0));
+            // bob holds no shares
+            assert!(!etf.can_close(accounts.bob, 0));
+            // vault 1 was never opened
+            assert!(!etf.can_close(accounts.alice, 1));
+        }
+
+        #[ink::test]
+        fn can_close_respects_cooldown_and_collateral() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            etf.set_min_hold_duration(100).unwrap();
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert!(!etf.can_close(accounts.alice, 0));
+            assert_eq!(etf.close_vault(0), Err(ContractError::CooldownActive));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(100);
+            assert!(etf.can_close(accounts.alice, 0));
+
+            // the books hold less than the vault pays out
+            etf.balances.insert(token, &5);
+            assert!(!etf.can_close(accounts.alice, 0));
+            assert_eq!(etf.close_vault(0), Err(ContractError::InsufficientCollateral));
+        }
+
+        #[ink::test]
+        fn export_and_import_vaults_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut old = new_etf(Vec::new(), Vec::new());
+            old.open_vault(accounts.alice, 0, None).unwrap();
+            old.open_vault(accounts.bob, 1, None).unwrap();
+            old.open_vault(accounts.alice, 2, None).unwrap();
+            old.close_vault(1).unwrap();
+
+            let exported = old.export_vaults().unwrap();
+            assert_eq!(exported, vec![(0, accounts.alice), (2, accounts.alice)]);
+
+            // the new instance lives at its own address
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.eve);
+            let mut new = new_etf(Vec::new(), Vec::new());
+            assert_eq!(new.import_vaults(exported.clone()), Ok(()));
+            assert_eq!(new.export_vaults(), Ok(exported.clone()));
+            assert_eq!(new.get_vaults_of_owner(accounts.alice), vec![0, 2]);
+            assert_eq!(new.get_vaults_quantity_per_owner(accounts.alice), 2);
+
+            // a second import could overwrite the migrated records
+            assert_eq!(new.import_vaults(exported), Err(ContractError::ImportNotAllowed));
+
+            // vaults opened afterwards do not collide with the imported ids
+            new.open_vault(accounts.bob, 3, None).unwrap();
+            assert_eq!(new.get_vault_owners(vec![3]), vec![Some(accounts.bob)]);
+        }
+
+        #[ink::test]
+        fn import_vaults_is_guarded() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(
+                etf.import_vaults(vec![(0, accounts.alice), (0, accounts.bob)]),
+                Err(ContractError::VaultAlreadyExists)
+            );
+            assert_eq!(etf.export_vaults(), Ok(Vec::new()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.export_vaults(), Err(ContractError::NotAuthorized));
+            assert_eq!(
+                etf.import_vaults(vec![(0, accounts.bob)]),
+                Err(ContractError::NotAuthorized)
+            );
+
+            // an instance that already opened vaults cannot import
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(
+                etf.import_vaults(vec![(5, accounts.bob)]),
+                Err(ContractError::ImportNotAllowed)
+            );
+        }
+
+        #[ink::test]
+        fn transfer_to_zero_address_is_rejected() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            let zero = AccountId::from([0u8; 32]);
+
+            assert_eq!(etf.transfer(zero, 1), Err(ContractError::ZeroAddress));
+            assert_eq!(etf.transfer_from(accounts.alice, zero, 1), Err(ContractError::ZeroAddress));
+            assert_eq!(etf.balance_of(zero), 0);
+        }
+
+        #[ink::test]
+        fn open_vault_draws_from_linked_escrow() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let escrow = AccountId::from([0x20; 32]);
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            assert_eq!(etf.set_escrow(Some(escrow)), Ok(()));
+            assert_eq!(etf.get_escrow(), Some(escrow));
+
+            // the first token sits in the escrow, the second only in alice's wallet
+            mock_erc20::deposit(escrow, first, accounts.alice, 10);
+            mock_erc20::mint(second, accounts.alice, 20);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 20);
+
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(mock_erc20::balance_of(first, escrow), 0);
+            assert_eq!(mock_erc20::balance_of(first, accounts.django), 10);
+            assert_eq!(mock_erc20::balance_of(second, accounts.django), 20);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 0);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_escrow(None), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn allowlist_restricts_transfers() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    enforce_allowlist: true,
+                    ..Default::default()
+                },
+            );
+            assert!(etf.is_allowlist_enforced());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // neither party is allowlisted yet
+            assert_eq!(etf.transfer(accounts.bob, 10), Err(ContractError::NotAllowlisted));
+            assert_eq!(etf.set_allowed(accounts.alice, true), Ok(()));
+            assert_eq!(etf.transfer(accounts.bob, 10), Err(ContractError::NotAllowlisted));
+
+            assert_eq!(etf.set_allowed(accounts.bob, true), Ok(()));
+            assert!(etf.is_allowed(accounts.bob));
+            assert_eq!(etf.transfer(accounts.bob, 10), Ok(SHARES - 10));
+            assert_eq!(
+                etf.transfer_from(accounts.bob, accounts.charlie, 5),
+                Err(ContractError::NotAllowlisted)
+            );
+
+            assert_eq!(etf.set_allowed(accounts.bob, false), Ok(()));
+            assert!(!etf.is_allowed(accounts.bob));
+            assert_eq!(etf.transfer(accounts.bob, 10), Err(ContractError::NotAllowlisted));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_allowed(accounts.bob, true), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn allowlist_is_ignored_when_not_enforced() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert!(!etf.is_allowlist_enforced());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert!(!etf.is_allowed(accounts.bob));
+            assert_eq!(etf.transfer(accounts.bob, 10), Ok(SHARES - 10));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.transfer_from(accounts.bob, accounts.charlie, 5), Ok(5));
+        }
+
+        #[ink::test]
+        fn state_is_updated_before_token_calls() {
+            use std::{cell::RefCell, rc::Rc};
+
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+
+            // record collateral, shares and the vault owner as each token call starts
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let log = seen.clone();
+            mock_erc20::on_invoke(move |_| {
+                let view = new_etf(Vec::new(), Vec::new());
+                log.borrow_mut().push((
+                    view.get_balance(token),
+                    view.balance_of(accounts.alice),
+                    view.get_vault_owners(vec![0])[0],
+                ));
+            });
+
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            // the vault is recorded before its collateral is pulled
+            assert_eq!(seen.borrow().len(), 3);
+            assert!(seen
+                .borrow()
+                .iter()
+                .all(|state| *state == (10, SHARES, Some(accounts.alice))));
+
+            seen.borrow_mut().clear();
+            etf.close_vault(0).unwrap();
+            // and settled before the collateral is paid out
+            assert_eq!(*seen.borrow(), vec![(0, 0, None)]);
+        }
+
+        #[ink::test]
+        fn config_changed_event_works() {
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.set_shares_per_vault(250).unwrap();
+            etf.set_min_collateral_ratio_bps(12_000).unwrap();
+
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            let mut changes = Vec::new();
+            for event in events.iter() {
+                match <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data") {
+                    Event::ConfigChanged(ConfigChanged { field, old, new }) => changes.push((field, old, new)),
+                    _ => panic!("expected ConfigChanged"),
+                }
+            }
+            assert_eq!(
+                changes,
+                vec![
+                    (*b"shares_per_vault", SHARES, 250),
+                    (*b"min_ratio_bps\0\0\0", 0, 12_000),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn vault_collateralization_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            mock_erc20::mint(first, accounts.alice, 20);
+            mock_erc20::mint(second, accounts.alice, 40);
+            mock_erc20::approve(first, accounts.alice, accounts.django, 20);
+            mock_erc20::approve(second, accounts.alice, accounts.django, 40);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.alice, 1, None).unwrap();
+            assert_eq!(etf.vault_collateralization(0), Ok(10_000));
+
+            // simulate an accounting error on the second token, 30 of the 40 needed
+            etf.balances.insert(second, &30);
+            assert_eq!(etf.vault_collateralization(1), Ok(7_500));
+            // what the vaults actually pay out is the requirement, not the basket
+            etf.vault_collateral.insert(1, &vec![10, 10]);
+            assert_eq!(etf.vault_collateralization(1), Ok(10_000));
+
+            assert_eq!(etf.vault_collateralization(2), Err(ContractError::VaultNotFound));
+        }
+
+        #[ink::test]
+        fn close_vault_charges_redemption_fee() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (token, stablecoin) = (AccountId::from([0x10; 32]), AccountId::from([0x30; 32]));
+            let mut etf = new_etf_with(
+                vec![token],
+                vec![10],
+                EtfConfig {
+                    fee_recipient: accounts.charlie,
+                    ..Default::default()
+                },
+            );
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(etf.set_redemption_fee(Some(stablecoin), 3), Ok(()));
+            assert_eq!(etf.get_redemption_fee(), (Some(stablecoin), 3));
+            mock_erc20::mint(stablecoin, accounts.alice, 5);
+            mock_erc20::approve(stablecoin, accounts.alice, accounts.django, 3);
+
+            assert_eq!(etf.close_vault(vault), Ok(()));
+            assert_eq!(mock_erc20::balance_of(stablecoin, accounts.alice), 2);
+            assert_eq!(mock_erc20::balance_of(stablecoin, accounts.charlie), 3);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 10);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_redemption_fee(None, 0), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn close_vault_requires_redemption_fee() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (token, stablecoin) = (AccountId::from([0x10; 32]), AccountId::from([0x30; 32]));
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.set_redemption_fee(Some(stablecoin), 3).unwrap();
+
+            // no stablecoin approved, so the underlying is never released
+            assert_eq!(
+                etf.close_vault(vault),
+                Err(ContractError::TokenTransferFailed { token: stablecoin })
+            );
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn burn_own_shares_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // shares end up credited to the escrow itself
+            assert_eq!(etf.transfer(accounts.django, 40), Ok(SHARES - 40));
+            assert_eq!(
+                etf.burn_own_shares(41),
+                Err(ContractError::InsufficientBalance)
+            );
+            assert_eq!(etf.burn_own_shares(30), Ok(()));
+            assert_eq!(etf.balance_of(accounts.django), 10);
+            assert_eq!(etf.total_supply(), SHARES - 30);
+            assert_eq!(etf.balance_of(accounts.alice), SHARES - 40);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.burn_own_shares(10), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn close_vault_pays_caller_or_owner() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            mock_erc20::mint(token, accounts.alice, 20);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+
+            for redeem_to_owner in [false, true] {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+                let mut etf = new_etf_with(
+                    vec![token],
+                    vec![10],
+                    EtfConfig {
+                        redeem_to_owner,
+                        ..Default::default()
+                    },
+                );
+                assert_eq!(etf.redeems_to_owner(), redeem_to_owner);
+                // alice owns the vault but bob holds its shares
+                let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+                etf.transfer(accounts.bob, SHARES).unwrap();
+
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+                assert_eq!(etf.close_vault(vault), Ok(()));
+                assert_eq!(etf.balance_of(accounts.bob), 0);
+            }
+            // the first vault paid bob as the redeemer, the second went back to alice
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 10);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 10);
+        }
+
+        #[ink::test]
+        fn emergency_withdraw_resyncs_collateral() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 20);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.alice, 1, None).unwrap();
+
+            // corrupt the accounting, then pull some of the real tokens out
+            etf.balances.insert(token, &999);
+            assert_eq!(etf.emergency_withdraw(token, accounts.eve, 5), Ok(15));
+            assert_eq!(mock_erc20::balance_of(token, accounts.eve), 5);
+            assert_eq!(etf.get_balance(token), 15);
+
+            assert_eq!(
+                etf.emergency_withdraw(accounts.frank, accounts.eve, 1),
+                Err(ContractError::UnsupportedToken)
+            );
+            assert_eq!(
+                etf.emergency_withdraw(token, AccountId::from([0u8; 32]), 1),
+                Err(ContractError::ZeroAddress)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.emergency_withdraw(token, accounts.bob, 1),
+                Err(ContractError::NotAuthorized)
+            );
+        }
+
+        #[ink::test]
+        fn open_instructions_match_config() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let etf = new_etf(vec![first, second], vec![10, 20]);
+
+            let (spender, amounts) = etf.open_instructions();
+            assert_eq!(spender, accounts.django);
+            assert_eq!(amounts, vec![(first, 10), (second, 20)]);
+            assert_eq!(
+                amounts.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+                etf.get_required_tokens()
+            );
+            assert_eq!(
+                amounts.iter().map(|(_, amount)| *amount).collect::<Vec<_>>(),
+                etf.get_required_balances()
+            );
+        }
+
+        #[ink::test]
+        fn close_vault_normalized_rescales_payouts() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            // one whole token each, quoted with 18 decimals
+            let (usd, eth) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let one: Balance = 1_000_000_000_000_000_000;
+            let mut etf = new_etf(vec![usd, eth], vec![one, one]);
+            for token in [usd, eth] {
+                mock_erc20::mint(token, accounts.alice, one);
+                mock_erc20::approve(token, accounts.alice, accounts.django, one);
+            }
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            assert_eq!(
+                etf.close_vault_normalized(vault, vec![6]),
+                Err(ContractError::LengthMismatch)
+            );
+            assert_eq!(
+                etf.close_vault_normalized(vault, vec![6, 18]),
+                Ok(vec![(usd, 1_000_000), (eth, one)])
+            );
+            assert_eq!(mock_erc20::balance_of(usd, accounts.alice), 1_000_000);
+            assert_eq!(mock_erc20::balance_of(eth, accounts.alice), one);
+            // the 6-decimal token's remainder is still collateral
+            assert_eq!(etf.balances.get(usd), Some(one - 1_000_000));
+            assert_eq!(etf.balances.get(eth), Some(0));
+            assert_eq!(etf.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn normalize_amount_works() {
+            assert_eq!(normalize_amount(1_500_000, 6, 18), Some(1_500_000_000_000_000_000));
+            assert_eq!(normalize_amount(1_500_000_000_000_000_000, 18, 6), Some(1_500_000));
+            assert_eq!(normalize_amount(1_999, 6, 3), Some(1));
+            assert_eq!(normalize_amount(7, 8, 8), Some(7));
+            assert_eq!(normalize_amount(Balance::MAX, 0, 18), None);
+        }
+
+        #[ink::test]
+        fn preview_close_all_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.alice, 1, None).unwrap();
+            etf.open_vault(accounts.bob, 2, None).unwrap();
+
+            let preview = etf.preview_close_all(accounts.alice).unwrap();
+            assert_eq!(preview, (2 * SHARES, vec![(first, 20), (second, 40)]));
+            assert_eq!(
+                etf.preview_close_all(accounts.charlie),
+                Ok((0, vec![(first, 0), (second, 0)]))
+            );
+
+            // the preview matches what closing actually burns and pays out
+            assert_eq!(etf.close_all_vaults(), Ok(vec![0, 1]));
+            assert_eq!(etf.balance_of(accounts.alice), 3 * SHARES - preview.0);
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 100 - 30 + 20);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 100 - 60 + 40);
+        }
+
+        #[ink::test]
+        fn open_vault_stops_at_trapping_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let tokens = [
+                AccountId::from([0x10; 32]),
+                AccountId::from([0x11; 32]),
+                AccountId::from([0x12; 32]),
+            ];
+            let mut etf = new_etf(tokens.to_vec(), vec![10, 20, 30]);
+            for token in tokens {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            // the mock reports a trapped call the same way as a rejected one
+            mock_erc20::reject_transfers(tokens[1]);
+
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::TokenTransferFailed { token: tokens[1] })
+            );
+            // the error is returned straight away, so the loop never reaches the third
+            // token; on-chain it also reverts the collateral credited before the calls
+            assert_eq!(mock_erc20::balance_of(tokens[2], accounts.alice), 100);
+            assert_eq!(mock_erc20::allowance(tokens[2], accounts.alice, accounts.django), 100);
+            assert_eq!(mock_erc20::balance_of(tokens[1], accounts.django), 0);
+        }
+
+        #[ink::test]
+        fn close_vault_respects_cooldown() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(etf.set_min_hold_duration(100), Ok(()));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1000);
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.vault_unlocks_at(vault), Some(1100));
+
+            // shortening the cooldown does not apply to vaults already open
+            assert_eq!(etf.set_min_hold_duration(0), Ok(()));
+            let unlocked = etf.open_vault(accounts.alice, 1, None).unwrap();
+            assert_eq!(etf.vault_unlocks_at(unlocked), None);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1099);
+            assert_eq!(etf.close_vault(vault), Err(ContractError::CooldownActive));
+            assert_eq!(etf.close_vault(unlocked), Ok(()));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1100);
+            assert_eq!(etf.close_vault(vault), Ok(()));
+            assert_eq!(etf.vault_unlocks_at(vault), None);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_min_hold_duration(1), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn operator_moves_and_redeems_shares() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 20);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            let vault = etf.open_vault(accounts.alice, 1, None).unwrap();
+
+            // bob is no operator yet
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.transfer_from(accounts.alice, accounts.charlie, 5),
+                Err(ContractError::NotAuthorized)
+            );
+            assert_eq!(etf.close_vault_from(accounts.alice, vault), Err(ContractError::NotAuthorized));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            etf.set_operator(accounts.bob, true);
+            assert!(etf.is_operator(accounts.alice, accounts.bob));
+            assert!(!etf.is_operator(accounts.bob, accounts.alice));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.transfer_from(accounts.alice, accounts.charlie, 5), Ok(2 * SHARES - 5));
+            assert_eq!(etf.balance_of(accounts.charlie), 5);
+            // the shares are burnt from alice and the collateral goes back to her
+            assert_eq!(etf.close_vault_from(accounts.alice, vault), Ok(()));
+            assert_eq!(etf.balance_of(accounts.alice), SHARES - 5);
+            assert_eq!(etf.balance_of(accounts.bob), 0);
+            assert_eq!(mock_erc20::balance_of(token, accounts.alice), 10);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            etf.set_operator(accounts.bob, false);
+            assert!(!etf.is_operator(accounts.alice, accounts.bob));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.transfer_from(accounts.alice, accounts.charlie, 5),
+                Err(ContractError::NotAuthorized)
+            );
+        }
+
+        #[ink::test]
+        fn share_metadata_works() {
+            let etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    share_name: "Aleph Index".to_string(),
+                    share_symbol: "ALX".to_string(),
+                    share_decimals: 6,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.get_name(), "Aleph Index");
+            assert_eq!(etf.get_symbol(), "ALX");
+            assert_eq!(etf.share_decimals(), 6);
+        }
+
+        #[ink::test]
+        fn vaults_redeem_their_recorded_collateral() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            let old = etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.get_vault_collateral(old), Some(vec![10, 20]));
+
+            // there is no basket setter yet, so change the config in place
+            etf.required_balances = vec![30, 5];
+            let new = etf.open_vault(accounts.alice, 1, None).unwrap();
+            assert_eq!(etf.get_vault_collateral(new), Some(vec![30, 5]));
+            assert_eq!(etf.preview_close_all(accounts.alice).unwrap().1, vec![(first, 40), (second, 25)]);
+
+            assert_eq!(etf.redeem(old), Ok(vec![(first, 10), (second, 20)]));
+            assert_eq!(etf.get_vault_collateral(old), None);
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 100 - 40 + 10);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 100 - 25 + 20);
+
+            assert_eq!(etf.redeem(new), Ok(vec![(first, 30), (second, 5)]));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 100);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 100);
+            assert_eq!(etf.balances.get(first), Some(0));
+            assert_eq!(etf.balances.get(second), Some(0));
+        }
+
+        #[ink::test]
+        fn redeemable_vaults_rounds_down_to_whole_vaults() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            for vault in 0..3 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+            // bob ends up with 2.5 vaults' worth of shares
+            etf.transfer(accounts.bob, 2 * SHARES + SHARES / 2).unwrap();
+
+            assert_eq!(
+                etf.redeemable_vaults(accounts.bob),
+                Ok((2, vec![(first, 20), (second, 40)]))
+            );
+            assert_eq!(
+                etf.redeemable_vaults(accounts.alice),
+                Ok((0, vec![(first, 0), (second, 0)]))
+            );
+        }
+
+        #[ink::test]
+        fn redeemable_vaults_rejects_overflowing_count() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(vec![AccountId::from([0x10; 32])], vec![10]);
+            etf.balances.insert(accounts.bob, &Balance::MAX);
+            assert_eq!(etf.redeemable_vaults(accounts.bob), Err(ContractError::Overflow));
+        }
+
+        #[ink::test]
+        fn redeemable_vaults_follows_recorded_collateral() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            for vault in 0..2 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+            // a single-token close elsewhere rebalanced what vault 0 pays out
+            etf.vault_collateral.insert(0, &vec![12, 18]);
+
+            assert_eq!(
+                etf.redeemable_vaults(accounts.alice),
+                Ok((2, vec![(first, 22), (second, 38)]))
+            );
+            assert_eq!(etf.close_vault(0), Ok(()));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 80 + 12);
+        }
+
+        #[ink::test]
+        fn admin_close_vault_requires_consent() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            // alice is the admin, bob owns the vault and holds its shares
+            let vault = etf.open_vault(accounts.bob, 0, None).unwrap();
+            etf.transfer(accounts.bob, SHARES).unwrap();
+
+            assert_eq!(etf.admin_close_vault(vault, accounts.bob), Err(ContractError::NotAuthorized));
+            assert_eq!(
+                etf.admin_close_vault(vault, accounts.charlie),
+                Err(ContractError::InsufficientBalance)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.approve(accounts.alice, SHARES), Ok(()));
+            // consenting to the admin does not let anyone else close it
+            assert_eq!(etf.admin_close_vault(vault, accounts.bob), Err(ContractError::NotAuthorized));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(etf.admin_close_vault(vault, accounts.bob), Ok(()));
+            assert_eq!(etf.balance_of(accounts.bob), 0);
+            assert_eq!(etf.allowance(accounts.bob, accounts.alice), 0);
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 10);
+            assert_eq!(etf.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn transfer_from_spends_allowance() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.approve(accounts.bob, 30), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                etf.transfer_from(accounts.alice, accounts.charlie, 31),
+                Err(ContractError::NotAuthorized)
+            );
+            assert_eq!(etf.transfer_from(accounts.alice, accounts.charlie, 20), Ok(SHARES - 20));
+            assert_eq!(etf.allowance(accounts.alice, accounts.bob), 10);
+            assert_eq!(etf.balance_of(accounts.charlie), 20);
+        }
+
+        #[ink::test]
+        fn share_allowance_events_work() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.approve(accounts.bob, 30), Ok(()));
+            assert_eq!(etf.increase_allowance(accounts.bob, 20), Ok(()));
+            assert_eq!(etf.decrease_allowance(accounts.bob, 10), Ok(()));
+            assert_eq!(
+                etf.decrease_allowance(accounts.bob, 41),
+                Err(ContractError::Overflow)
+            );
+            assert_eq!(etf.allowance(accounts.alice, accounts.bob), 40);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            etf.transfer_from(accounts.alice, accounts.charlie, 15).unwrap();
+
+            let mut approvals = Vec::new();
+            let mut used = Vec::new();
+            for event in ink::env::test::recorded_events() {
+                match <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data") {
+                    Event::Approval(Approval { owner, spender, value }) => approvals.push((owner, spender, value)),
+                    Event::AllowanceUsed(AllowanceUsed { owner, spender, spent, remaining }) => {
+                        used.push((owner, spender, spent, remaining))
+                    }
+                    _ => {}
+                }
+            }
+            assert_eq!(
+                approvals,
+                vec![
+                    (accounts.alice, accounts.bob, 30),
+                    (accounts.alice, accounts.bob, 50),
+                    (accounts.alice, accounts.bob, 40),
+                ]
+            );
+            assert_eq!(used, vec![(accounts.alice, accounts.bob, 15, 25)]);
+        }
+
+        #[ink::test]
+        fn open_vault_enforces_min_basket_value() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let first = AccountId::from([0x10; 32]);
+            let second = AccountId::from([0x11; 32]);
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 40);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 40);
+            }
+            mock_erc20::set_price(oracle, first, 3);
+            mock_erc20::set_price(oracle, second, 7);
+            etf.set_price_oracle(Some(oracle)).unwrap();
+
+            // the basket is worth 10 * 3 + 20 * 7 = 170
+            assert_eq!(etf.set_min_basket_value(171), Ok(()));
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::BelowMinBasket)
+            );
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 40);
+
+            assert_eq!(etf.set_min_basket_value(170), Ok(()));
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(etf.set_min_basket_value(169), Ok(()));
+            assert_eq!(etf.open_vault(accounts.alice, 1, None), Ok(1));
+            assert_eq!(etf.get_min_basket_value(), 169);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.set_min_basket_value(0), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn min_basket_value_checks_each_token_without_oracle() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let first = AccountId::from([0x10; 32]);
+            let second = AccountId::from([0x11; 32]);
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 40);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 40);
+            }
+
+            // the smallest amount in the basket is 10
+            assert_eq!(etf.set_min_basket_value(11), Ok(()));
+            assert_eq!(
+                etf.open_vault(accounts.alice, 0, None),
+                Err(ContractError::BelowMinBasket)
+            );
+            assert_eq!(etf.set_min_basket_value(10), Ok(()));
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(etf.set_min_basket_value(9), Ok(()));
+            assert_eq!(etf.open_vault(accounts.alice, 1, None), Ok(1));
+        }
+
+        #[ink::test]
+        fn closed_vault_ids_are_not_reused() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(0));
+            assert_eq!(etf.open_vault(accounts.alice, 1, None), Ok(1));
+            assert_eq!(etf.close_vault(0), Ok(()));
+            assert_eq!(etf.get_vaults_quantity(), 1);
+
+            // the live count went down but the next id did not
+            assert_eq!(etf.get_next_vault_id(), 2);
+            assert_eq!(etf.open_vault(accounts.alice, 0, None), Ok(2));
+            assert_eq!(etf.get_vaults_quantity(), 2);
+            assert_eq!(etf.get_vaults_of_owner(accounts.alice), vec![1, 2]);
+        }
+
+        #[ink::test]
+        fn close_vault_applies_fee_tiers() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf_with(
+                vec![token],
+                vec![1_000],
+                EtfConfig {
+                    fee_recipient: accounts.eve,
+                    fee_tiers: vec![(0, 100), (5_000, 50), (20_000, 10)],
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.get_fee_tiers(), vec![(0, 100), (5_000, 50), (20_000, 10)]);
+            mock_erc20::mint(token, accounts.alice, 3_000);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 3_000);
+            etf.set_price_oracle(Some(oracle)).unwrap();
+            for vault in 0..3 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+
+            // each vault redeems 1000 tokens, its value depends on the price
+            let expected_fees = [(4, 10), (5, 5), (25, 1)];
+            for (vault, (price, fee)) in expected_fees.iter().enumerate() {
+                mock_erc20::set_price(oracle, token, *price);
+                let before = mock_erc20::balance_of(token, accounts.alice);
+                assert_eq!(etf.redeem(vault as u8), Ok(vec![(token, 1_000 - fee)]));
+                assert_eq!(mock_erc20::balance_of(token, accounts.alice), before + 1_000 - fee);
+            }
+            assert_eq!(mock_erc20::balance_of(token, accounts.eve), 10 + 5 + 1);
+            assert_eq!(mock_erc20::balance_of(token, accounts.django), 0);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "fee tiers must be sorted by threshold")]
+        fn unsorted_fee_tiers_are_rejected() {
+            new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    fee_tiers: vec![(5_000, 50), (0, 100)],
+                    ..Default::default()
+                },
+            );
+        }
+
+        #[ink::test]
+        fn close_vault_single_pays_one_token() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let first = AccountId::from([0x10; 32]);
+            let second = AccountId::from([0x11; 32]);
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            for vault in 0..4 {
+                etf.open_vault(accounts.alice, vault, None).unwrap();
+            }
+            assert_eq!(etf.close_vault_single(0, first, 0), Err(ContractError::OracleNotSet));
+
+            mock_erc20::set_price(oracle, first, 2);
+            mock_erc20::set_price(oracle, second, 3);
+            etf.set_price_oracle(Some(oracle)).unwrap();
+            assert_eq!(etf.set_single_token_penalty_bps(100), Ok(()));
+            assert_eq!(etf.close_vault_single(0, first, 50), Err(ContractError::PenaltyTooLow));
+            assert_eq!(
+                etf.close_vault_single(0, first, MAX_BPS + 1),
+                Err(ContractError::InvalidPenalty)
+            );
+            assert_eq!(
+                etf.close_vault_single(0, accounts.eve, 100),
+                Err(ContractError::UnsupportedToken)
+            );
+
+            // 10 * 2 + 20 * 3 = 80 of value is 40 of the first token, less 5%
+            assert_eq!(etf.close_vault_single(0, first, 500), Ok(38));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 60 + 38);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 20);
+            assert_eq!(etf.get_balance(first), 40 - 38);
+            assert_eq!(etf.get_balance(second), 80);
+            assert_eq!(etf.balance_of(accounts.alice), 3 * SHARES);
+            // the other vaults give up the 28 drawn beyond the vault's own 10 and split
+            // its 20 of the second token, so their snapshots still add up to the escrow
+            assert_eq!(etf.get_vault_collateral(1), Some(vec![0, 27]));
+            assert_eq!(etf.get_vault_collateral(2), Some(vec![1, 27]));
+            assert_eq!(etf.get_vault_collateral(3), Some(vec![1, 26]));
+            assert_eq!(etf.close_vault(3), Ok(()));
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 20 + 26);
+        }
+
+        #[ink::test]
+        fn close_vault_single_forfeits_the_last_basket() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let first = AccountId::from([0x10; 32]);
+            let second = AccountId::from([0x11; 32]);
+            let oracle = AccountId::from([0x20; 32]);
+            let mut etf = new_etf_with(
+                vec![first, second],
+                vec![10, 20],
+                EtfConfig {
+                    fee_recipient: accounts.bob,
+                    ..Default::default()
+                },
+            );
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            mock_erc20::set_price(oracle, first, 2);
+            mock_erc20::set_price(oracle, second, 3);
+            etf.set_price_oracle(Some(oracle)).unwrap();
+
+            // no other vault can cover the 28 of the first token beyond its own 10
+            assert_eq!(
+                etf.close_vault_single(0, first, 500),
+                Err(ContractError::InsufficientCollateral)
+            );
+            // 80 of value is 40 of the first token, less 90%
+            assert_eq!(etf.close_vault_single(0, first, 9_000), Ok(4));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 90 + 4);
+            assert_eq!(mock_erc20::balance_of(first, accounts.bob), 6);
+            assert_eq!(mock_erc20::balance_of(second, accounts.bob), 20);
+            assert_eq!(etf.get_balance(first), 0);
+            assert_eq!(etf.get_balance(second), 0);
+        }
+
+        #[ink::test]
+        fn disabled_events_skip_informational_ones() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf_with(
+                vec![token],
+                vec![1_000],
+                EtfConfig {
+                    protocol_fee_bps: 500,
+                    fee_recipient: accounts.bob,
+                    fee_tiers: vec![(0, 100)],
+                    emit_events: false,
+                    ..Default::default()
+                },
+            );
+            mock_erc20::mint(token, accounts.alice, 2_000);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 2_000);
+            etf.set_min_hold_duration(0).unwrap();
+            etf.set_operator(accounts.bob, true);
+            etf.approve(accounts.charlie, 10).unwrap();
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            etf.open_vault(accounts.alice, 1, None).unwrap();
+            etf.close_vault(0).unwrap();
+            etf.resync_collateral(token).unwrap();
+            // the fees are still charged, only their events are skipped
+            assert_eq!(etf.balance_of(accounts.bob), 2 * SHARES / 20);
+            assert_eq!(mock_erc20::balance_of(token, accounts.bob), 10);
+
+            // config, operator, fee and resync events are skipped
+            let events: Vec<_> = ink::env::test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data"))
+                .collect();
+            assert_eq!(events.len(), 4);
+            assert!(matches!(events[0], Event::Approval(_)));
+            assert!(matches!(events[1], Event::VaultOpened(_)));
+            assert!(matches!(events[2], Event::VaultOpened(_)));
+            assert!(matches!(events[3], Event::VaultClosed(_)));
+        }
+
+        #[ink::test]
+        fn resync_collateral_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let token = AccountId::from([0x10; 32]);
+            let mut etf = new_etf(vec![token], vec![10]);
+            mock_erc20::mint(token, accounts.alice, 10);
+            mock_erc20::approve(token, accounts.alice, accounts.django, 10);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // the books claim more than the escrow holds
+            etf.balances.insert(token, &50);
+            assert_eq!(etf.resync_collateral(accounts.eve), Err(ContractError::UnsupportedToken));
+            assert_eq!(etf.resync_collateral(token), Ok(()));
+            assert_eq!(etf.reconcile(), vec![(token, 10, 10)]);
+
+            let resynced: Vec<_> = ink::env::test::recorded_events()
+                .filter_map(|event| match <Event as scale::Decode>::decode(&mut &event.data[..]) {
+                    Ok(Event::CollateralResynced(CollateralResynced { token, old, new })) => {
+                        Some((token, old, new))
+                    }
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(resynced, vec![(token, 50, 10)]);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(etf.resync_collateral(token), Err(ContractError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn vault_closed_event_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf(Vec::new(), Vec::new());
+            let vault = etf.open_vault(accounts.bob, 0, None).unwrap();
+            // alice holds the shares of the vault bob owns
+            etf.close_vault(vault).unwrap();
+
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            let event = <Event as scale::Decode>::decode(&mut &events[1].data[..])
+                .expect("invalid event data");
+            match event {
+                Event::VaultClosed(closed) => {
+                    assert_eq!(closed.vault, vault);
+                    assert_eq!(closed.owner, accounts.bob);
+                    assert_eq!(closed.redeemer, accounts.alice);
+                    assert_eq!(closed.shares_burned, SHARES);
+                }
+                _ => panic!("expected VaultClosed"),
+            }
+            // signature topic plus the vault, owner and redeemer topics
+            assert_eq!(events[1].topics.len(), 4);
+        }
+
+        // an escrow at django whose vault 0 holds 10 + 20 of two tokens, and a router
+        // paying 2 and 3 of a stable token for them
+        fn swappable_etf() -> (EtfEscrow, [AccountId; 3], AccountId) {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let tokens = [
+                AccountId::from([0x10; 32]),
+                AccountId::from([0x11; 32]),
+                AccountId::from([0x12; 32]),
+            ];
+            let router = AccountId::from([0x30; 32]);
+            let mut etf = new_etf(vec![tokens[0], tokens[1]], vec![10, 20]);
+            for token in [tokens[0], tokens[1]] {
+                mock_erc20::mint(token, accounts.alice, 20);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 20);
+            }
+            mock_erc20::mint(tokens[2], router, 1_000);
+            mock_erc20::set_swap_rate(router, tokens[0], tokens[2], 2);
+            mock_erc20::set_swap_rate(router, tokens[1], tokens[2], 3);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            (etf, tokens, router)
+        }
+
+        #[ink::test]
+        fn close_vault_to_token_swaps_basket() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut etf, tokens, router) = swappable_etf();
+
+            // 10 * 2 + 20 * 3 = 80 of the stable token
+            assert_eq!(etf.close_vault_to_token(0, tokens[2], 80, router), Ok(80));
+            assert_eq!(mock_erc20::balance_of(tokens[2], accounts.alice), 80);
+            assert_eq!(mock_erc20::balance_of(tokens[2], accounts.django), 0);
+            assert_eq!(mock_erc20::balance_of(tokens[0], router), 10);
+            assert_eq!(mock_erc20::balance_of(tokens[1], router), 20);
+            assert_eq!(etf.balance_of(accounts.alice), 0);
+            assert_eq!(etf.get_vaults_quantity(), 0);
+        }
+
+        #[ink::test]
+        fn close_vault_to_token_keeps_basket_output() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (mut etf, tokens, router) = swappable_etf();
+            mock_erc20::mint(tokens[0], router, 1_000);
+            mock_erc20::set_swap_rate(router, tokens[1], tokens[0], 4);
+
+            // the first token is paid out as is, only the second one is swapped
+            assert_eq!(etf.close_vault_to_token(0, tokens[0], 90, router), Ok(10 + 80));
+            assert_eq!(mock_erc20::balance_of(tokens[0], accounts.alice), 10 + 10 + 80);
+            assert_eq!(mock_erc20::balance_of(tokens[0], accounts.django), 0);
+        }
+
+        #[ink::test]
+        fn close_vault_to_token_enforces_min_out() {
+            let (mut etf, tokens, router) = swappable_etf();
+            // on-chain the error reverts the close and the swaps with it
+            assert_eq!(
+                etf.close_vault_to_token(0, tokens[2], 81, router),
+                Err(ContractError::SlippageExceeded)
+            );
+            assert_eq!(
+                etf.close_vault_to_token(1, tokens[2], 0, router),
+                Err(ContractError::VaultNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn close_vault_returns_deposited_amounts_after_config_change() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(accounts.django);
+            let (first, second) = (AccountId::from([0x10; 32]), AccountId::from([0x11; 32]));
+            let mut etf = new_etf(vec![first, second], vec![10, 20]);
+            for token in [first, second] {
+                mock_erc20::mint(token, accounts.alice, 100);
+                mock_erc20::approve(token, accounts.alice, accounts.django, 100);
+            }
+            let vault = etf.open_vault(accounts.alice, 0, None).unwrap();
+
+            // there is no basket setter yet, so change the config in place
+            etf.required_balances = vec![1, 50];
+            etf.open_vault(accounts.alice, 1, None).unwrap();
+            assert_eq!(etf.close_vault(vault), Ok(()));
+            assert_eq!(mock_erc20::balance_of(first, accounts.alice), 100 - 11 + 10);
+            assert_eq!(mock_erc20::balance_of(second, accounts.alice), 100 - 70 + 20);
+            // what is left backs exactly the other vault
+            assert_eq!(etf.balances.get(first), Some(1));
+            assert_eq!(etf.balances.get(second), Some(50));
+
+            // a snapshot that no longer lines up with the basket is rejected
+            etf.required_tokens.push(AccountId::from([0x12; 32]));
+            assert_eq!(etf.close_vault(1), Err(ContractError::LengthMismatch));
+        }
+
+        #[ink::test]
+        fn fractional_shares_redeem_when_recombined() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = new_etf_with(
+                Vec::new(),
+                Vec::new(),
+                EtfConfig {
+                    share_decimals: 6,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(etf.share_precision(), 1_000_000);
+            etf.open_vault(accounts.alice, 0, None).unwrap();
+            assert_eq!(etf.whole_balance_of(accounts.alice), (SHARES, 0));
+
+            // 1.25 and 0.75 shares go to bob and charlie
+            etf.transfer(accounts.bob, 1_250_000).unwrap();
+            etf.transfer(accounts.charlie, 750_000).unwrap();
+            assert_eq!(etf.whole_balance_of(accounts.alice), (SHARES - 2, 0));
+            assert_eq!(etf.whole_balance_of(accounts.bob), (1, 250_000));
+            assert_eq!(etf.whole_balance_of(accounts.charlie), (0, 750_000));
+            assert_eq!(etf.close_vault(0), Err(ContractError::InsufficientBalance));
+
+            // recombining the fractions makes the vault redeemable again
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            etf.transfer(accounts.alice, 750_000).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            etf.transfer(accounts.alice, 1_250_000).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(etf.close_vault(0), Ok(()));
+            assert_eq!(etf.balance_of(accounts.alice), 0);
+            assert_eq!(etf.total_supply(), 0);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use fungibleToken::{Erc20 as _, FungibleTokenRef};
+        use ink_e2e::build_message;
+        use fee_token::FeeTokenRef;
+        use price_oracle::PriceOracleRef;
+        use swap_router::SwapRouterRef;
+        use escrow::EscrowRef;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        // instantiates an escrow over `tokens` paying its fees to `fee_recipient`,
+        // with every other constructor argument at the unit tests' defaults
+        async fn instantiate_etf(
+            client: &mut ink_e2e::Client<ink_e2e::PolkadotConfig, ink::env::DefaultEnvironment>,
+            tokens: Vec<AccountId>,
+            balances: Vec<Balance>,
+            fee_recipient: AccountId,
+        ) -> AccountId {
+            let etf_constructor = EtfEscrowRef::new(
+                tokens,
+                balances,
+                None,
+                0,
+                fee_recipient,
+                "XTF-Index-0".to_string(),
+                "XTF".to_string(),
+                0,
+                false,
+                false,
+                Vec::new(),
+                true,
+            );
+            client
+                .instantiate("etf", &ink_e2e::alice(), etf_constructor, 0, None)
+                .await
+                .expect("etf instantiate failed")
+                .account_id
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn missing_collateral_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let first_constructor =
+                FungibleTokenRef::new("First".to_string(), "FST".to_string(), 1000, false, None, 0);
+            let first = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), first_constructor, 0, None)
+                .await
+                .expect("first token instantiate failed")
+                .account_id;
+            let second_constructor =
+                FungibleTokenRef::new("Second".to_string(), "SND".to_string(), 5, false, None, 0);
+            let second = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), second_constructor, 0, None)
+                .await
+                .expect("second token instantiate failed")
+                .account_id;
+
+            let etf = instantiate_etf(
+                &mut client,
+                vec![first, second],
+                vec![100, 10],
+                ink_e2e::account_id(ink_e2e::AccountKeyring::Alice),
+            )
+            .await;
+
+            // alice holds enough of the first token but only half of the second
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let missing = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.missing_collateral(alice));
+            let missing = client
+                .call_dry_run(&ink_e2e::alice(), &missing, 0, None)
+                .await
+                .return_value();
+            assert_eq!(missing, vec![(first, 0), (second, 5)]);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn reconcile_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("First".to_string(), "FST".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(etf.clone(), 200, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            for vault in 0..2 {
+                let open = build_message::<EtfEscrowRef>(etf.clone())
+                    .call(|etf| etf.open_vault(alice, vault, None));
+                client
+                    .call(&ink_e2e::alice(), open, 0, None)
+                    .await
+                    .expect("open_vault failed");
+            }
+
+            let reconcile = build_message::<EtfEscrowRef>(etf.clone()).call(|etf| etf.reconcile());
+            let reconcile = client
+                .call_dry_run(&ink_e2e::alice(), &reconcile, 0, None)
+                .await
+                .return_value();
+            assert_eq!(reconcile, vec![(token, 200, 200)]);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn absorb_surplus_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("First".to_string(), "FST".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            // send tokens straight to the escrow, bypassing open_vault
+            let transfer = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.transfer(etf.clone(), 40));
+            client
+                .call(&ink_e2e::alice(), transfer, 0, None)
+                .await
+                .expect("transfer failed");
+
+            let absorb = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.absorb_surplus(token));
+            let absorbed = client
+                .call(&ink_e2e::alice(), absorb, 0, None)
+                .await
+                .expect("absorb_surplus failed")
+                .return_value();
+            assert_eq!(absorbed, Ok(40));
+
+            let reconcile = build_message::<EtfEscrowRef>(etf.clone()).call(|etf| etf.reconcile());
+            let reconcile = client
+                .call_dry_run(&ink_e2e::alice(), &reconcile, 0, None)
+                .await
+                .return_value();
+            assert_eq!(reconcile, vec![(token, 40, 40)]);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml ../price_oracle/Cargo.toml")]
+        async fn nav_in_reference_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("First".to_string(), "FST".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let oracle_constructor = PriceOracleRef::new();
+            let oracle = client
+                .instantiate("price_oracle", &ink_e2e::alice(), oracle_constructor, 0, None)
+                .await
+                .expect("oracle instantiate failed")
+                .account_id;
+            let set_price = build_message::<PriceOracleRef>(oracle.clone())
+                .call(|oracle| oracle.set_price(token, 3));
+            client
+                .call(&ink_e2e::alice(), set_price, 0, None)
+                .await
+                .expect("set_price failed");
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(etf.clone(), 100, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed");
+            let set_oracle = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.set_price_oracle(Some(oracle)));
+            client
+                .call(&ink_e2e::alice(), set_oracle, 0, None)
+                .await
+                .expect("set_price_oracle failed");
+
+            let nav = build_message::<EtfEscrowRef>(etf.clone()).call(|etf| etf.nav_in_reference());
+            let nav = client
+                .call_dry_run(&ink_e2e::alice(), &nav, 0, None)
+                .await
+                .return_value();
+            assert_eq!(nav, Ok(300));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fee_token/Cargo.toml")]
+        async fn open_vault_rejects_fee_on_transfer_token(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            // every transfer of this token burns 1 unit on the way
+            let token_constructor = FeeTokenRef::new(1000, 1);
+            let token = client
+                .instantiate("fee_token", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            let opened = client
+                .call_dry_run(&ink_e2e::alice(), &open, 0, None)
+                .await
+                .return_value();
+            assert_eq!(opened, Err(ContractError::TransferShortfall));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml ../escrow/Cargo.toml")]
+        async fn open_vault_draws_from_escrow_works(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let escrow_constructor = EscrowRef::new(vec![token], 0, false, true);
+            let escrow = client
+                .instantiate("escrow", &ink_e2e::alice(), escrow_constructor, 0, None)
+                .await
+                .expect("escrow instantiate failed")
+                .account_id;
+
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            // alice deposits the collateral into the escrow
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(escrow, 100, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            let deposit = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.deposit(token, 100));
+            client
+                .call(&ink_e2e::alice(), deposit, 0, None)
+                .await
+                .expect("deposit failed");
+
+            // link the two contracts both ways
+            let set_operator = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.set_operator(Some(etf)));
+            client
+                .call(&ink_e2e::alice(), set_operator, 0, None)
+                .await
+                .expect("set_operator failed");
+            let set_escrow = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.set_escrow(Some(escrow)));
+            client
+                .call(&ink_e2e::alice(), set_escrow, 0, None)
+                .await
+                .expect("set_escrow failed");
+
+            // no approval to the etf is needed, the vault is funded from the deposit
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            let opened = client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed")
+                .return_value();
+            assert_eq!(opened, Ok(0));
+
+            let deposited = build_message::<EscrowRef>(escrow.clone())
+                .call(|escrow| escrow.get_deposited(token, alice));
+            let deposited = client
+                .call_dry_run(&ink_e2e::alice(), &deposited, 0, None)
+                .await
+                .return_value();
+            assert_eq!(deposited, 0);
+
+            let held = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.balance_of(etf));
+            let held = client
+                .call_dry_run(&ink_e2e::alice(), &held, 0, None)
+                .await
+                .return_value();
+            assert_eq!(held, 100);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn close_vault_charges_fee_in_stablecoin(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let stablecoin_constructor =
+                FungibleTokenRef::new("Stablecoin".to_string(), "USD".to_string(), 1000, false, None, 0);
+            let stablecoin = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), stablecoin_constructor, 0, None)
+                .await
+                .expect("stablecoin instantiate failed")
+                .account_id;
+
+            // fees go to bob
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], bob).await;
+
+            let set_fee = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.set_redemption_fee(Some(stablecoin), 25));
+            client
+                .call(&ink_e2e::alice(), set_fee, 0, None)
+                .await
+                .expect("set_redemption_fee failed");
+
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(etf, 100, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed");
+
+            // the redeemer approves the fee in the stablecoin, not the underlying
+            let approve_fee = build_message::<FungibleTokenRef>(stablecoin.clone())
+                .call(|stablecoin| stablecoin.approve(etf, 25, None));
+            client
+                .call(&ink_e2e::alice(), approve_fee, 0, None)
+                .await
+                .expect("approve fee failed");
+            let close = build_message::<EtfEscrowRef>(etf.clone()).call(|etf| etf.close_vault(0));
+            let closed = client
+                .call(&ink_e2e::alice(), close, 0, None)
+                .await
+                .expect("close_vault failed")
+                .return_value();
+            assert_eq!(closed, Ok(()));
+
+            let fee_paid = build_message::<FungibleTokenRef>(stablecoin.clone())
+                .call(|stablecoin| stablecoin.balance_of(bob));
+            let fee_paid = client
+                .call_dry_run(&ink_e2e::alice(), &fee_paid, 0, None)
+                .await
+                .return_value();
+            assert_eq!(fee_paid, 25);
+
+            let underlying = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.balance_of(alice));
+            let underlying = client
+                .call_dry_run(&ink_e2e::alice(), &underlying, 0, None)
+                .await
+                .return_value();
+            assert_eq!(underlying, 1000);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn shares_conform_to_erc20(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            // an empty basket mints shares without any collateral transfers
+            let etf = instantiate_etf(&mut client, Vec::new(), Vec::new(), alice).await;
+            for vault in 0..2 {
+                let open = build_message::<EtfEscrowRef>(etf.clone())
+                    .call(|etf| etf.open_vault(alice, vault, None));
+                client
+                    .call(&ink_e2e::alice(), open, 0, None)
+                    .await
+                    .expect("open_vault failed");
+            }
+
+            // from here on the escrow is only seen through the `Erc20` trait,
+            // the way another contract holding its address would call it
+            type ShareToken = ink::contract_ref!(Erc20);
+
+            let total_supply = build_message::<ShareToken>(etf.clone())
+                .call(|token| token.total_supply());
+            let total_supply = client
+                .call_dry_run(&ink_e2e::alice(), &total_supply, 0, None)
+                .await
+                .return_value();
+            assert_eq!(total_supply, 2 * SHARES);
+
+            let transfer = build_message::<ShareToken>(etf.clone())
+                .call(|token| token.transfer(bob, 30));
+            let remaining = client
+                .call(&ink_e2e::alice(), transfer, 0, None)
+                .await
+                .expect("transfer failed")
+                .return_value();
+            assert_eq!(remaining, Ok(2 * SHARES - 30));
+
+            for (owner, expected) in [(alice, 2 * SHARES - 30), (bob, 30)] {
+                let balance = build_message::<ShareToken>(etf.clone())
+                    .call(|token| token.balance_of(owner));
+                let balance = client
+                    .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                    .await
+                    .return_value();
+                assert_eq!(balance, expected);
+            }
+
+            // a transfer moves shares without changing the supply
+            let total_supply = build_message::<ShareToken>(etf.clone())
+                .call(|token| token.total_supply());
+            let total_supply = client
+                .call_dry_run(&ink_e2e::alice(), &total_supply, 0, None)
+                .await
+                .return_value();
+            assert_eq!(total_supply, 2 * SHARES);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn emergency_withdraw_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(etf, 100, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed");
+
+            // only the admin may bypass the accounting
+            let withdraw = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.emergency_withdraw(token, bob, 40));
+            let rejected = client
+                .call_dry_run(&ink_e2e::bob(), &withdraw, 0, None)
+                .await
+                .return_value();
+            assert_eq!(rejected, Err(ContractError::NotAuthorized));
+
+            let collateral = client
+                .call(&ink_e2e::alice(), withdraw, 0, None)
+                .await
+                .expect("emergency_withdraw failed")
+                .return_value();
+            assert_eq!(collateral, Ok(60));
+
+            let received = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.balance_of(bob));
+            let received = client
+                .call_dry_run(&ink_e2e::alice(), &received, 0, None)
+                .await
+                .return_value();
+            assert_eq!(received, 40);
+
+            let recorded = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.get_balance(token));
+            let recorded = client
+                .call_dry_run(&ink_e2e::alice(), &recorded, 0, None)
+                .await
+                .return_value();
+            assert_eq!(recorded, 60);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn external_supply_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1234, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let etf = instantiate_etf(&mut client, Vec::new(), Vec::new(), alice).await;
+
+            let supply = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.external_supply(token));
+            let supply = client
+                .call_dry_run(&ink_e2e::alice(), &supply, 0, None)
+                .await
+                .return_value();
+            assert_eq!(supply, 1234);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn controls_token_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let etf = instantiate_etf(&mut client, Vec::new(), Vec::new(), alice).await;
+
+            let controls = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.controls_token(token));
+            let controlled = client
+                .call_dry_run(&ink_e2e::alice(), &controls, 0, None)
+                .await
+                .return_value();
+            assert!(!controlled);
+
+            // alice deployed the token and hands it over to the escrow
+            let set_owner = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.set_owner(etf));
+            client
+                .call(&ink_e2e::alice(), set_owner, 0, None)
+                .await
+                .expect("set_owner failed");
+            let controlled = client
+                .call_dry_run(&ink_e2e::alice(), &controls, 0, None)
+                .await
+                .return_value();
+            assert!(controlled);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn probe_token_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let token_constructor =
+                FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+            let etf = instantiate_etf(&mut client, Vec::new(), Vec::new(), alice).await;
+
+            let probe = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.probe_token(token));
+            let probe = client
+                .call_dry_run(&ink_e2e::alice(), &probe, 0, None)
+                .await
+                .return_value();
+            assert!(probe);
+
+            // a plain account has no code to answer the call
+            let bogus = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let probe = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.probe_token(bogus));
+            let probe = client
+                .call_dry_run(&ink_e2e::alice(), &probe, 0, None)
+                .await
+                .return_value();
+            assert!(!probe);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml ../price_oracle/Cargo.toml")]
+        async fn close_vault_single_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let mut tokens = Vec::new();
+            for symbol in ["AAA", "BBB"] {
+                let token_constructor =
+                    FungibleTokenRef::new("MyToken".to_string(), symbol.to_string(), 1000, false, None, 0);
+                let token = client
+                    .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                    .await
+                    .expect("token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+
+            let oracle_constructor = PriceOracleRef::new();
+            let oracle = client
+                .instantiate("price_oracle", &ink_e2e::alice(), oracle_constructor, 0, None)
+                .await
+                .expect("oracle instantiate failed")
+                .account_id;
+            for (token, price) in tokens.iter().zip([2, 3]) {
+                let set_price = build_message::<PriceOracleRef>(oracle.clone())
+                    .call(|oracle| oracle.set_price(*token, price));
+                client
+                    .call(&ink_e2e::alice(), set_price, 0, None)
+                    .await
+                    .expect("set_price failed");
+            }
+
+            let etf = instantiate_etf(&mut client, tokens.clone(), vec![10, 20], alice).await;
+
+            for token in tokens.iter() {
+                let approve = build_message::<FungibleTokenRef>(token.clone())
+                    .call(|token| token.approve(etf.clone(), 100, None));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+            for vault in 0..4 {
+                let open = build_message::<EtfEscrowRef>(etf.clone())
+                    .call(|etf| etf.open_vault(alice, vault, None));
+                client
+                    .call(&ink_e2e::alice(), open, 0, None)
+                    .await
+                    .expect("open_vault failed");
+            }
+
+            // pricing the other basket tokens needs the oracle
+            let first = tokens[0];
+            let close = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.close_vault_single(0, first, 0));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &close, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(ContractError::OracleNotSet));
+
+            let set_oracle = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.set_price_oracle(Some(oracle)));
+            client
+                .call(&ink_e2e::alice(), set_oracle, 0, None)
+                .await
+                .expect("set_price_oracle failed");
+
+            // 10 * 2 + 20 * 3 = 80 of value is 40 of the first token, less 5%,
+            // 28 of it taken from the other vaults in exchange for the second token
+            let close = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.close_vault_single(0, first, 500));
+            client
+                .call(&ink_e2e::alice(), close, 0, None)
+                .await
+                .expect("close_vault_single failed");
+
+            let balance = build_message::<FungibleTokenRef>(first.clone())
+                .call(|token| token.balance_of(alice));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 1000 - 40 + 38);
+
+            let collateral = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.get_vault_collateral(1));
+            let collateral = client
+                .call_dry_run(&ink_e2e::alice(), &collateral, 0, None)
+                .await
+                .return_value();
+            assert_eq!(collateral, Some(vec![0, 27]));
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml")]
+        async fn resync_collateral_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_constructor =
+                FungibleTokenRef::new("First".to_string(), "FST".to_string(), 1000, false, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let etf = instantiate_etf(&mut client, vec![token], vec![100], alice).await;
+
+            let approve = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.approve(etf.clone(), 100, None));
+            client
+                .call(&ink_e2e::alice(), approve, 0, None)
+                .await
+                .expect("approve failed");
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed");
+
+            // drift the actual balance away from the books
+            let transfer = build_message::<FungibleTokenRef>(token.clone())
+                .call(|token| token.transfer(etf.clone(), 25));
+            client
+                .call(&ink_e2e::alice(), transfer, 0, None)
+                .await
+                .expect("transfer failed");
+            let reconcile = build_message::<EtfEscrowRef>(etf.clone()).call(|etf| etf.reconcile());
+            let drifted = client
+                .call_dry_run(&ink_e2e::alice(), &reconcile, 0, None)
+                .await
+                .return_value();
+            assert_eq!(drifted, vec![(token, 100, 125)]);
+
+            let resync = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.resync_collateral(token));
+            let resynced = client
+                .call(&ink_e2e::alice(), resync, 0, None)
+                .await
+                .expect("resync_collateral failed")
+                .return_value();
+            assert_eq!(resynced, Ok(()));
+
+            let reconciled = client
+                .call_dry_run(&ink_e2e::alice(), &reconcile, 0, None)
+                .await
+                .return_value();
+            assert_eq!(reconciled, vec![(token, 125, 125)]);
+
+            Ok(())
+        }
+
+        #[ink_e2e::test(additional_contracts = "../fungible_token/Cargo.toml ../swap_router/Cargo.toml")]
+        async fn close_vault_to_token_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            let mut tokens = Vec::new();
+            for symbol in ["AAA", "BBB", "USD"] {
+                let token_constructor =
+                    FungibleTokenRef::new("MyToken".to_string(), symbol.to_string(), 1000, false, None, 0);
+                let token = client
+                    .instantiate("fungibleToken", &ink_e2e::alice(), token_constructor, 0, None)
+                    .await
+                    .expect("token instantiate failed")
+                    .account_id;
+                tokens.push(token);
+            }
+            let stable = tokens[2];
+
+            let router_constructor = SwapRouterRef::new();
+            let router = client
+                .instantiate("swap_router", &ink_e2e::alice(), router_constructor, 0, None)
+                .await
+                .expect("router instantiate failed")
+                .account_id;
+            for (token, rate) in tokens.iter().zip([2, 3]) {
+                let set_rate = build_message::<SwapRouterRef>(router.clone())
+                    .call(|router| router.set_rate(*token, stable, rate));
+                client
+                    .call(&ink_e2e::alice(), set_rate, 0, None)
+                    .await
+                    .expect("set_rate failed");
+            }
+            // the router pays out of its own stable token liquidity
+            let fund = build_message::<FungibleTokenRef>(stable.clone())
+                .call(|token| token.transfer(router.clone(), 500));
+            client
+                .call(&ink_e2e::alice(), fund, 0, None)
+                .await
+                .expect("transfer failed");
+
+            let etf =
+                instantiate_etf(&mut client, vec![tokens[0], tokens[1]], vec![10, 20], alice).await;
+            for token in tokens[..2].iter() {
+                let approve = build_message::<FungibleTokenRef>(token.clone())
+                    .call(|token| token.approve(etf.clone(), 100, None));
+                client
+                    .call(&ink_e2e::alice(), approve, 0, None)
+                    .await
+                    .expect("approve failed");
+            }
+            let open = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.open_vault(alice, 0, None));
+            client
+                .call(&ink_e2e::alice(), open, 0, None)
+                .await
+                .expect("open_vault failed");
+
+            // 10 * 2 + 20 * 3 = 80, asking for more reverts
+            let close = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.close_vault_to_token(0, stable, 81, router));
+            let result = client
+                .call_dry_run(&ink_e2e::alice(), &close, 0, None)
+                .await
+                .return_value();
+            assert_eq!(result, Err(ContractError::SlippageExceeded));
+
+            let close = build_message::<EtfEscrowRef>(etf.clone())
+                .call(|etf| etf.close_vault_to_token(0, stable, 80, router));
+            let result = client
+                .call(&ink_e2e::alice(), close, 0, None)
+                .await
+                .expect("close_vault_to_token failed")
+                .return_value();
+            assert_eq!(result, Ok(80));
+
+            let balance = build_message::<FungibleTokenRef>(stable.clone())
+                .call(|token| token.balance_of(alice));
+            let balance = client
+                .call_dry_run(&ink_e2e::alice(), &balance, 0, None)
+                .await
+                .return_value();
+            assert_eq!(balance, 1000 - 500 + 80);
+
+            Ok(())
+        }
+    }
 }
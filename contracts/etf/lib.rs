@@ -15,6 +15,7 @@ mod etf {
     // const shares per vault
     const SHARES: Balance = 100;
     const TRANSFER_FROM_SELECTOR: [u8; 4] = [0, 0, 0, 6];
+    const VERSION: u32 = 1;
 
     #[ink::trait_definition]
     pub trait Erc20 {
@@ -63,6 +64,7 @@ mod etf {
         TransferFailed,
         CloseVaultFailed,
         VaultAlreadyExists,
+        ZeroAddress,
     }
 
     #[ink(storage)]
@@ -77,6 +79,14 @@ mod etf {
     }
 
     impl Etf {
+        // sending to the all-zeros account would burn shares without reducing the supply
+        fn ensure_non_zero(to: AccountId) -> Result<(), ContractError> {
+            if to == AccountId::from([0u8; 32]) {
+                return Err(ContractError::ZeroAddress);
+            }
+            Ok(())
+        }
+
         #[ink(constructor)]
         pub fn new(required_tokens: Vec<AccountId>, required_balances: Vec<Balance>) -> Self {
             Self {
@@ -102,12 +112,12 @@ mod etf {
 
         #[ink(message)]
         pub fn get_vault_owner(&self, vault: u8) -> AccountId {
-            self.vaults.get(&vault).unwrap()
+            self.vaults.get(vault).unwrap()
         }
 
         #[ink(message)]
         pub fn get_vaults_quantity_per_owner(&self, owner: AccountId) -> u8 {
-            self.vaults_quantity_per_owner.get(&owner).unwrap_or(0)
+            self.vaults_quantity_per_owner.get(owner).unwrap_or(0)
         }
 
         #[ink(message)]
@@ -115,16 +125,21 @@ mod etf {
             self.vaults_quantity
         }
 
+        #[ink(message)]
+        pub fn version(&self) -> u32 {
+            VERSION
+        }
+
         #[ink(message)]
         pub fn get_balance(&self, owner: AccountId) -> Balance {
-            self.balances.get(&owner).unwrap_or(0)
+            self.balances.get(owner).unwrap_or(0)
         }
 
         #[ink(message)]
         pub fn open_vault(&mut self, vault: u8) -> Result<u8, ContractError> {
             let caller = self.env().caller();
 
-            if self.vaults.contains(&vault) {
+            if self.vaults.contains(vault) {
                 return Err(ContractError::VaultAlreadyExists);
             }
 
@@ -154,7 +169,7 @@ mod etf {
             self.vaults.insert(vault, &caller);
             self.vaults_quantity += 1;
             let vaults_quantity_of_caller =
-                self.vaults_quantity_per_owner.get(&caller).unwrap_or(0);
+                self.vaults_quantity_per_owner.get(caller).unwrap_or(0);
             self.vaults_quantity_per_owner
                 .insert(caller, &(vaults_quantity_of_caller + 1));
 
@@ -169,7 +184,7 @@ mod etf {
         #[ink(message)]
         pub fn close_vault(&mut self, vault: u8) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            let owner = self.vaults.get(&vault).unwrap();
+            let owner = self.vaults.get(vault).unwrap();
 
             // check the caller has enough shares to close the vault and reedem the tokens
             let caller_shares_balance = self.balances.get(caller).unwrap_or(0);
@@ -202,8 +217,8 @@ mod etf {
                     .insert(token, &(escrow_balance - self.required_balances[i]));
             }
 
-            self.vaults.remove(&vault);
-            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(&owner).unwrap_or(0);
+            self.vaults.remove(vault);
+            let vaults_quantity_of_owner = self.vaults_quantity_per_owner.get(owner).unwrap_or(0);
             self.vaults_quantity_per_owner
                 .insert(owner, &(vaults_quantity_of_owner - 1));
             self.env().emit_event(VaultClosed { vault, owner });
@@ -214,17 +229,17 @@ mod etf {
     impl Erc20 for Etf {
         #[ink(message)]
         fn get_name(&self) -> String {
-            return String::from("X-ETF-INDEX-0");
+            String::from("X-ETF-INDEX-0")
         }
 
         #[ink(message)]
         fn get_symbol(&self) -> String {
-            return String::from("XTF");
+            String::from("XTF")
         }
 
         #[ink(message)]
         fn balance_of(&self, owner: AccountId) -> Balance {
-            self.balances.get(&owner).unwrap_or(0)
+            self.balances.get(owner).unwrap_or(0)
         }
 
         #[ink(message)]
@@ -240,6 +255,7 @@ mod etf {
 
         #[ink(message)]
         fn transfer(&mut self, to: AccountId, value: Balance) -> Result<Balance, ContractError> {
+            Self::ensure_non_zero(to)?;
             let from = self.env().caller();
             let from_balance = self.balance_of(from);
             if from_balance < value {
@@ -260,6 +276,7 @@ mod etf {
             to: AccountId,
             value: Balance,
         ) -> Result<Balance, ContractError> {
+            Self::ensure_non_zero(to)?;
             // let caller = self.env().caller();
             // TO-DO: need to check if the caller is allowed to transfer from `from`
 
@@ -275,4 +292,25 @@ mod etf {
             Ok(self.balance_of(from))
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn version_works() {
+            let etf = Etf::new(Vec::new(), Vec::new());
+            assert_eq!(etf.version(), 1);
+        }
+
+        #[ink::test]
+        fn transfer_to_zero_address_is_rejected() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut etf = Etf::new(Vec::new(), Vec::new());
+            let zero = AccountId::from([0u8; 32]);
+
+            assert_eq!(etf.transfer(zero, 0), Err(ContractError::ZeroAddress));
+            assert_eq!(etf.transfer_from(accounts.alice, zero, 0), Err(ContractError::ZeroAddress));
+        }
+    }
 }
@@ -0,0 +1,78 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::fee_token::{Erc20, FeeToken, FeeTokenRef};
+
+/// A deliberately hostile token for tests: every transfer burns a fixed fee,
+/// so recipients receive less than the amount sent.
+#[ink::contract]
+mod fee_token {
+    use ink::storage::Mapping;
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        InsufficientBalance,
+    }
+
+    #[ink::trait_definition]
+    pub trait Erc20 {
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance;
+        // no allowance checks, the stub only exercises fee accounting
+        #[ink(message, selector = 6)]
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<Balance, Error>;
+    }
+
+    #[ink(storage)]
+    pub struct FeeToken {
+        balances: Mapping<AccountId, Balance>,
+        // amount burnt from every transfer
+        fee: Balance,
+    }
+
+    impl FeeToken {
+        #[ink(constructor)]
+        pub fn new(total_supply: Balance, fee: Balance) -> Self {
+            let mut balances = Mapping::new();
+            balances.insert(Self::env().caller(), &total_supply);
+            Self { balances, fee }
+        }
+    }
+
+    impl Erc20 for FeeToken {
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(owner).unwrap_or(0)
+        }
+
+        #[ink(message, selector = 6)]
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<Balance, Error> {
+            let from_balance = self.balance_of(from);
+            if from_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+            self.balances.insert(from, &(from_balance - value));
+            let to_balance = self.balance_of(to);
+            self.balances.insert(to, &(to_balance + value.saturating_sub(self.fee)));
+            Ok(self.balance_of(from))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn transfer_from_burns_fee() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut token = FeeToken::new(100, 2);
+
+            assert_eq!(token.transfer_from(accounts.alice, accounts.bob, 10), Ok(90));
+            assert_eq!(token.balance_of(accounts.bob), 8);
+            assert_eq!(
+                token.transfer_from(accounts.bob, accounts.alice, 9),
+                Err(Error::InsufficientBalance)
+            );
+        }
+    }
+}
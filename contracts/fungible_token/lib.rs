@@ -1,12 +1,24 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::fungible_token::{
+    Erc20, Error, FungibleToken, FungibleTokenRef, ALLOWANCE_SELECTOR, TRANSFER_FROM_SELECTOR,
+};
+
 #[ink::contract]
 mod fungible_token {
     
+    use ink::env::{
+        call::{build_call, ExecutionInput, Selector},
+        DefaultEnvironment,
+    };
     use ink::storage::Mapping;
     use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
 
     pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0, 0, 0, 6];
+    pub const ALLOWANCE_SELECTOR: [u8; 4] = [0, 0, 0, 7];
+    pub const VERSION: u32 = 1;
+    pub const ON_TOKEN_RECEIVED_SELECTOR: [u8; 4] = ink::selector_bytes!("on_token_received");
 
     #[ink::trait_definition]
     pub trait Erc20 {
@@ -24,8 +36,37 @@ mod fungible_token {
         fn get_owner(&self) -> AccountId;
         #[ink(message, selector = 6)]
         fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<Balance, Error>;
+        // queried by `Escrow::escrow_allowance` through this fixed selector
+        #[ink(message, selector = 7)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance;
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: Balance, expiry: Option<Timestamp>) -> Result<(), Error>;
     }
-    
+
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceUsed {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        spent: Balance,
+        remaining: Balance,
+    }
+
+    // an allowance and the time after which it can no longer be spent
+    pub type Allowance = (Balance, Option<Timestamp>);
+
+    // total, start, duration and amount already claimed of a linear vesting schedule
+    pub type VestingSchedule = (Balance, Timestamp, Timestamp, Balance);
 
     #[ink(storage)]
     pub struct FungibleToken {
@@ -34,39 +75,364 @@ mod fungible_token {
         owner: AccountId,
         total_supply: Balance,
         balances: Mapping<AccountId, Balance>,
+        allowances: Mapping<(AccountId, AccountId), Allowance>,
+        // notify contract recipients of transfers
+        call_receiver: bool,
+        // fail transfers whose recipient notification fails
+        strict_receiver: bool,
+        // accounts with a non-zero balance, for explorers
+        holders: Vec<AccountId>,
+        // set once the initial supply has been credited
+        initialized: bool,
+        vesting: Mapping<AccountId, VestingSchedule>,
+        // how long tokens minted with `mint_to` stay locked, zero disables it
+        lockup_duration: Timestamp,
+        // minted tokens an account cannot send before the given time
+        locked: Mapping<AccountId, (Balance, Timestamp)>,
+        // proposed owner that has not accepted yet
+        pending_owner: Option<AccountId>,
     }
 
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum Error {
         InsufficientBalance,
+        InsufficientAllowance,
+        ReceiverRejected,
+        AllowanceExpired,
+        ZeroAddress,
+        NoVestingSchedule,
+        DuplicateHolder,
+        Overflow,
+        LockedUp,
+        InvalidAmount,
+        AlreadyInitialized,
     }
 
     impl FungibleToken {
         /// Constructor that initializes the `FungibleToken`.
+        ///
+        /// `initial_allowance` lets a spender such as an escrow draw from the
+        /// owner's supply right after deployment. Tokens minted with `mint_to`
+        /// cannot be moved for `lockup_duration` afterwards.
         #[ink(constructor)]
-        pub fn new(name: String, symbol: String, total_supply: Balance) -> Self {
-            let mut balances = Mapping::new();
-            let owner = Self::env().caller();
-            
-            balances.insert(owner, &total_supply);
-            Self {
+        pub fn new(
+            name: String,
+            symbol: String,
+            total_supply: Balance,
+            call_receiver: bool,
+            initial_allowance: Option<(AccountId, Balance)>,
+            lockup_duration: Timestamp,
+        ) -> Self {
+            let mut token = Self {
                 name,
                 symbol,
-                owner,
-                total_supply,
-                balances,
+                owner: Self::env().caller(),
+                total_supply: 0,
+                balances: Mapping::new(),
+                allowances: Mapping::new(),
+                call_receiver,
+                strict_receiver: false,
+                holders: Vec::new(),
+                initialized: false,
+                vesting: Mapping::new(),
+                lockup_duration,
+                locked: Mapping::new(),
+                pending_owner: None,
+            };
+            token
+                .initialize(total_supply)
+                .expect("a fresh token is not initialized");
+            if let Some((spender, value)) = initial_allowance {
+                let owner = token.owner;
+                token.allowances.insert((owner, spender), &(value, None::<Timestamp>));
+                Self::env().emit_event(Approval { owner, spender, value });
+            }
+            token
+        }
+
+        /// Constructor crediting each of `holders` at genesis, with the total
+        /// supply set to the sum of their balances.
+        #[ink(constructor)]
+        pub fn new_with_balances(
+            name: String,
+            symbol: String,
+            holders: Vec<(AccountId, Balance)>,
+        ) -> Result<Self, Error> {
+            let mut token = Self::new(name, symbol, 0, false, None, 0);
+            let mut total_supply: Balance = 0;
+            for (i, (holder, balance)) in holders.iter().enumerate() {
+                if holders[..i].iter().any(|(other, _)| other == holder) {
+                    return Err(Error::DuplicateHolder);
+                }
+                total_supply = total_supply.checked_add(*balance).ok_or(Error::Overflow)?;
+                token.set_balance(*holder, *balance);
+            }
+            token.total_supply = total_supply;
+            Ok(token)
+        }
+
+        // sending to the all-zeros account would burn tokens without reducing the supply
+        fn ensure_non_zero(to: AccountId) -> Result<(), Error> {
+            if to == AccountId::from([0u8; 32]) {
+                return Err(Error::ZeroAddress);
+            }
+            Ok(())
+        }
+
+        // no balance can exceed the supply, so a larger amount means corrupted balances
+        fn ensure_within_supply(&self, value: Balance) -> Result<(), Error> {
+            if value > self.total_supply {
+                return Err(Error::InvalidAmount);
+            }
+            Ok(())
+        }
+
+        /// Credits the initial supply to the owner. The constructor already does
+        /// this, so any later call fails with `AlreadyInitialized`.
+        #[ink(message)]
+        pub fn initialize(&mut self, total_supply: Balance) -> Result<(), Error> {
+            if self.initialized {
+                return Err(Error::AlreadyInitialized);
             }
+            self.initialized = true;
+            self.total_supply = total_supply;
+            self.set_balance(self.owner, total_supply);
+            Ok(())
+        }
+
+        // every balance change goes through here to keep `holders` in sync
+        fn set_balance(&mut self, account: AccountId, balance: Balance) {
+            let previous = self.balance_of(account);
+            if previous == 0 && balance > 0 {
+                self.holders.push(account);
+            } else if previous > 0 && balance == 0 {
+                if let Some(index) = self.holders.iter().position(|holder| *holder == account) {
+                    self.holders.swap_remove(index);
+                }
+            }
+            self.balances.insert(account, &balance);
+        }
+
+        #[ink(message)]
+        pub fn holders_paged(&self, start: u32, limit: u32) -> Vec<(AccountId, Balance)> {
+            self.holders
+                .iter()
+                .skip(start as usize)
+                .take(limit as usize)
+                .map(|holder| (*holder, self.balance_of(*holder)))
+                .collect()
+        }
+
+        /// Every current holder with their balance, e.g. for an airdrop.
+        ///
+        /// Reads one balance per holder, so the cost grows with the holder list;
+        /// past a few thousand holders use `holders_paged` instead.
+        #[ink(message)]
+        pub fn snapshot_holders(&self) -> Vec<(AccountId, Balance)> {
+            self.holders_paged(0, u32::MAX)
         }
 
         #[ink(message)]
         pub fn mint_to(&mut self, to: AccountId, value: Balance) {
             let caller = self.env().caller();
             assert_eq!(caller, self.owner);
+            // a zero mint must not lock up anything
+            if value == 0 {
+                return;
+            }
+            self.mint(to, value);
+            // only the minted tokens are locked; ones still locked from an earlier
+            // mint stay locked until the new end
+            if self.lockup_duration > 0 {
+                let locked_until = self.env().block_timestamp().saturating_add(self.lockup_duration);
+                let locked = self.locked_balance(to).saturating_add(value);
+                self.locked.insert(to, &(locked, locked_until));
+            }
+        }
+
+        #[ink(message)]
+        pub fn locked_until(&self, account: AccountId) -> Option<Timestamp> {
+            self.locked.get(account).map(|(_, locked_until)| locked_until)
+        }
+
+        /// Part of `account`'s balance that cannot be sent yet.
+        #[ink(message)]
+        pub fn locked_balance(&self, account: AccountId) -> Balance {
+            match self.locked.get(account) {
+                Some((locked, locked_until)) if self.env().block_timestamp() < locked_until => locked,
+                _ => 0,
+            }
+        }
+
+        // tokens minted under a lock-up cannot leave the account before it ends
+        fn ensure_unlocked(&self, from: AccountId, value: Balance) -> Result<(), Error> {
+            let balance = self.balance_of(from);
+            // an overdraft is left to the balance check
+            if value <= balance && value > balance.saturating_sub(self.locked_balance(from)) {
+                return Err(Error::LockedUp);
+            }
+            Ok(())
+        }
+
+        fn mint(&mut self, to: AccountId, value: Balance) {
+            // increase total supply, every balance is bounded by it
+            self.total_supply = self
+                .total_supply
+                .checked_add(value)
+                .expect("total supply overflow");
             let to_balance = self.balance_of(to);
-            self.balances.insert(to, &(to_balance + value));
-            // increase total supply
-            self.total_supply += value;
+            self.set_balance(to, to_balance + value);
+        }
+
+        /// Lets `beneficiary` mint up to `total` tokens, released linearly
+        /// over `duration` from `start`. Replaces any previous schedule.
+        #[ink(message)]
+        pub fn vest(&mut self, beneficiary: AccountId, total: Balance, start: Timestamp, duration: Timestamp) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.vesting.insert(beneficiary, &(total, start, duration, 0));
+        }
+
+        #[ink(message)]
+        pub fn get_vesting(&self, beneficiary: AccountId) -> Option<VestingSchedule> {
+            self.vesting.get(beneficiary)
+        }
+
+        // part of the schedule released at `now`, claimed or not
+        fn vested_at(schedule: &VestingSchedule, now: Timestamp) -> Result<Balance, Error> {
+            let (total, start, duration, _) = *schedule;
+            let elapsed = now.saturating_sub(start);
+            if elapsed >= duration {
+                return Ok(total);
+            }
+            total
+                .checked_mul(Balance::from(elapsed))
+                .map(|released| released / Balance::from(duration))
+                .ok_or(Error::Overflow)
+        }
+
+        /// Mints the caller's vested but unclaimed tokens, returning the amount minted.
+        #[ink(message)]
+        pub fn claim_vested(&mut self) -> Result<Balance, Error> {
+            let caller = self.env().caller();
+            let schedule = self.vesting.get(caller).ok_or(Error::NoVestingSchedule)?;
+            let (total, start, duration, claimed) = schedule;
+            let claimable = Self::vested_at(&schedule, self.env().block_timestamp())? - claimed;
+            if claimable > 0 {
+                self.vesting
+                    .insert(caller, &(total, start, duration, claimed + claimable));
+                self.mint(caller, claimable);
+            }
+            Ok(claimable)
+        }
+
+        #[ink(message)]
+        pub fn burn(&mut self, value: Balance) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let caller_balance = self.balance_of(caller);
+            if caller_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+            self.set_balance(caller, caller_balance - value);
+            self.total_supply -= value;
+            Ok(())
+        }
+
+        /// Hands the token over to `new_owner`, e.g. an escrow that mints against it.
+        #[ink(message)]
+        pub fn set_owner(&mut self, new_owner: AccountId) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.owner = new_owner;
+            // a proposal made by the previous owner no longer stands
+            self.pending_owner = None;
+        }
+
+        /// Proposes `new_owner`, who takes over only once they call `accept_ownership`.
+        #[ink(message)]
+        pub fn propose_owner(&mut self, new_owner: AccountId) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.pending_owner = Some(new_owner);
+        }
+
+        /// Withdraws a proposal that has not been accepted yet.
+        #[ink(message)]
+        pub fn cancel_ownership_proposal(&mut self) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.pending_owner = None;
+        }
+
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) {
+            let caller = self.env().caller();
+            assert_eq!(Some(caller), self.pending_owner);
+            self.owner = caller;
+            self.pending_owner = None;
+        }
+
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner
+        }
+
+        #[ink(message)]
+        pub fn set_strict_receiver(&mut self, strict: bool) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.strict_receiver = strict;
+        }
+
+        fn notify_receiver(&self, from: AccountId, to: AccountId, value: Balance) -> Result<(), Error> {
+            let result = build_call::<DefaultEnvironment>()
+                .call(to)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ON_TOKEN_RECEIVED_SELECTOR))
+                        .push_arg(from)
+                        .push_arg(value),
+                )
+                .returns::<()>()
+                .try_invoke();
+
+            // a failing receiver only blocks the transfer in strict mode
+            match result {
+                Ok(Ok(())) => Ok(()),
+                _ if !self.strict_receiver => Ok(()),
+                _ => Err(Error::ReceiverRejected),
+            }
+        }
+
+        /// Like `balance_of`, but `None` for an account that never held tokens.
+        #[ink(message)]
+        pub fn try_balance_of(&self, account: AccountId) -> Option<Balance> {
+            self.balances.get(account)
+        }
+
+        #[ink(message)]
+        pub fn balance_and_allowance(&self, owner: AccountId, spender: AccountId) -> (Balance, Balance) {
+            (self.balance_of(owner), self.allowance(owner, spender))
+        }
+
+        /// Drops the allowance the caller gave `spender`, same as approving zero.
+        #[ink(message)]
+        pub fn revoke_allowance(&mut self, spender: AccountId) {
+            let owner = self.env().caller();
+            self.allowances.remove((owner, spender));
+            self.env().emit_event(Approval { owner, spender, value: 0 });
+        }
+
+        #[ink(message)]
+        pub fn allowance_expiry(&self, owner: AccountId, spender: AccountId) -> Option<Timestamp> {
+            self.allowances.get((owner, spender)).and_then(|(_, expiry)| expiry)
+        }
+
+        #[ink(message)]
+        pub fn version(&self) -> u32 {
+            VERSION
         }
     }
 
@@ -74,12 +440,12 @@ mod fungible_token {
 
         #[ink(message)]
         fn get_name(&self) -> String {
-            return self.name.clone();
+            self.name.clone()
         }
 
         #[ink(message)]
         fn get_symbol(&self) -> String {
-            return self.symbol.clone();
+            self.symbol.clone()
         }
 
         #[ink(message)]
@@ -89,7 +455,7 @@ mod fungible_token {
 
         #[ink(message)]
         fn balance_of(&self, account: AccountId) -> Balance {
-            self.balances.get(&account).unwrap_or_default()
+            self.balances.get(account).unwrap_or_default()
         }
 
         #[ink(message)]
@@ -99,41 +465,97 @@ mod fungible_token {
 
         #[ink(message)]
         fn transfer(&mut self, to: AccountId, value: Balance) -> Result<Balance, Error> {
+            Self::ensure_non_zero(to)?;
+            self.ensure_within_supply(value)?;
             let from = self.env().caller();
+            self.ensure_unlocked(from, value)?;
             let from_balance = self.balance_of(from);
             if from_balance < value {
                 return Err(Error::InsufficientBalance);
             }
+
+            // read the recipient after debiting so a self-transfer is a no-op
+            self.set_balance(from, from_balance - value);
             let to_balance = self.balance_of(to);
+            self.set_balance(to, to_balance + value);
 
-            self.balances.insert(from, &(from_balance - value));
-            self.balances.insert(to, &(to_balance + value));
+            if self.call_receiver && self.env().is_contract(&to) {
+                self.notify_receiver(from, to, value)?;
+            }
 
             Ok(self.balance_of(from))
         }
 
         #[ink(message, selector = 6)]
         fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<Balance, Error> {
-            // let caller = self.env().caller();
-            // TO-DO: need to check if the caller is allowed to transfer from `from`
+            Self::ensure_non_zero(to)?;
+            self.ensure_within_supply(value)?;
+            self.ensure_unlocked(from, value)?;
+            let caller = self.env().caller();
+            // an account moving its own tokens does not need an allowance
+            let expiry = self.allowance_expiry(from, caller);
+            if caller != from && expiry.is_some_and(|expiry| self.env().block_timestamp() > expiry) {
+                return Err(Error::AllowanceExpired);
+            }
+            let allowance = self.allowance(from, caller);
+            if caller != from && allowance < value {
+                return Err(Error::InsufficientAllowance);
+            }
 
             let from_balance = self.balance_of(from);
             if from_balance < value {
                 return Err(Error::InsufficientBalance);
             }
-            let to_balance = self.balance_of(to);
 
-            self.balances.insert(from, &(from_balance - value));
-            self.balances.insert(to, &(to_balance + value));
+            // read the recipient after debiting so a self-transfer is a no-op
+            self.set_balance(from, from_balance - value);
+            let to_balance = self.balance_of(to);
+            self.set_balance(to, to_balance + value);
+            // a `Balance::MAX` allowance is treated as infinite and never decremented
+            if caller != from {
+                let remaining = if allowance == Balance::MAX {
+                    allowance
+                } else {
+                    self.allowances
+                        .insert((from, caller), &(allowance - value, expiry));
+                    allowance - value
+                };
+                self.env().emit_event(AllowanceUsed {
+                    owner: from,
+                    spender: caller,
+                    spent: value,
+                    remaining,
+                });
+            }
 
             Ok(self.balance_of(from))
         }
+
+        #[ink(message, selector = 7)]
+        fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            match self.allowances.get((owner, spender)) {
+                // an expired allowance can no longer be spent
+                Some((_, Some(expiry))) if self.env().block_timestamp() > expiry => 0,
+                Some((allowance, _)) => allowance,
+                None => 0,
+            }
+        }
+
+        #[ink(message)]
+        fn approve(&mut self, spender: AccountId, value: Balance, expiry: Option<Timestamp>) -> Result<(), Error> {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), &(value, expiry));
+            self.env().emit_event(Approval { owner, spender, value });
+            Ok(())
+        }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
 
+        type Event = <FungibleToken as ink::reflect::ContractEventBase>::Type;
+
         #[ink::test]
         fn total_supply_works() {
             let name = "MyToken".to_string();
@@ -142,7 +564,10 @@ mod fungible_token {
             let mytoken = FungibleToken::new(
                 name,
                 symbol,
-                total_supply
+                total_supply,
+                false,
+                None,
+                0
             );
             assert_eq!(mytoken.total_supply(), 100);
         }
@@ -155,7 +580,10 @@ mod fungible_token {
             let mytoken = FungibleToken::new(
                 name,
                 symbol,
-                total_supply
+                total_supply,
+                false,
+                None,
+                0
             );
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             assert_eq!(mytoken.balance_of(accounts.alice), 100);
@@ -171,7 +599,10 @@ mod fungible_token {
             let mut mytoken = FungibleToken::new(
                 name,
                 symbol,
-                total_supply
+                total_supply,
+                false,
+                None,
+                0
             );
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
 
@@ -193,7 +624,10 @@ mod fungible_token {
             let mut mytoken = FungibleToken::new(
                 name,
                 symbol,
-                total_supply);
+                total_supply,
+                false,
+                None,
+                0);
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
 
             assert_eq!(mytoken.balance_of(accounts.bob), 0);
@@ -201,5 +635,835 @@ mod fungible_token {
             assert_eq!(mytoken.balance_of(accounts.bob), quantity_to_bob);
             assert_eq!(mytoken.total_supply(), total_supply + quantity_to_bob);
         }
+
+        #[ink::test]
+        fn transfer_from_works() {
+            let name = "MyToken".to_string();
+            let symbol = "MTK".to_string();
+            let total_supply = 100;
+            let mut mytoken = FungibleToken::new(
+                name,
+                symbol,
+                total_supply,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(mytoken.approve(accounts.bob, 30, None), Ok(()));
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 30);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                mytoken.transfer_from(accounts.alice, accounts.charlie, 31),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                mytoken.transfer_from(accounts.alice, accounts.charlie, 20),
+                Ok(total_supply - 20)
+            );
+            assert_eq!(mytoken.balance_of(accounts.charlie), 20);
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 10);
+        }
+
+        #[ink::test]
+        fn infinite_allowance_is_not_decremented() {
+            let name = "MyToken".to_string();
+            let symbol = "MTK".to_string();
+            let total_supply = 100;
+            let mut mytoken = FungibleToken::new(
+                name,
+                symbol,
+                total_supply,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(mytoken.approve(accounts.bob, Balance::MAX, None), Ok(()));
+            assert_eq!(mytoken.approve(accounts.charlie, 50, None), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            for _ in 0..3 {
+                assert!(mytoken.transfer_from(accounts.alice, accounts.bob, 10).is_ok());
+                assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), Balance::MAX);
+            }
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert!(mytoken.transfer_from(accounts.alice, accounts.charlie, 10).is_ok());
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.charlie), 40);
+        }
+
+        #[ink::test]
+        fn version_works() {
+            let mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert_eq!(mytoken.version(), 1);
+        }
+
+        #[ink::test]
+        fn balance_and_allowance_works() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(mytoken.approve(accounts.bob, 25, None), Ok(()));
+            assert_eq!(mytoken.balance_and_allowance(accounts.alice, accounts.bob), (100, 25));
+            assert_eq!(mytoken.balance_and_allowance(accounts.bob, accounts.alice), (0, 0));
+        }
+
+        #[ink::test]
+        fn transfer_to_account_skips_receiver_hook() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                true,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            mytoken.set_strict_receiver(true);
+            assert_eq!(mytoken.transfer(accounts.bob, 10), Ok(90));
+            assert_eq!(mytoken.balance_of(accounts.bob), 10);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn set_strict_receiver_is_owner_only() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                true,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.set_strict_receiver(true);
+        }
+
+        #[derive(Debug, Clone, Copy)]
+        enum Op {
+            Transfer { from: usize, to: usize, value: Balance },
+            MintTo { to: usize, value: Balance },
+            Burn { from: usize, value: Balance },
+        }
+
+        // xorshift64, deterministic so a failing seed can be replayed
+        fn next(state: &mut u64) -> u64 {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            *state
+        }
+
+        fn random_ops(seed: u64, len: usize, holders: usize) -> Vec<Op> {
+            let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+            (0..len)
+                .map(|_| {
+                    let holder = (next(&mut state) % holders as u64) as usize;
+                    let other = (next(&mut state) % holders as u64) as usize;
+                    // mostly small values, with the occasional huge one to probe overflows
+                    let value = match next(&mut state) % 8 {
+                        0 => Balance::MAX - Balance::from(next(&mut state) % 4),
+                        _ => Balance::from(next(&mut state) % 200),
+                    };
+                    match next(&mut state) % 3 {
+                        0 => Op::Transfer { from: holder, to: other, value },
+                        1 => Op::MintTo { to: holder, value },
+                        _ => Op::Burn { from: holder, value },
+                    }
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn balances_always_sum_to_total_supply() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let holders = [accounts.alice, accounts.bob, accounts.charlie, accounts.django, accounts.eve];
+
+            for seed in 0..200u64 {
+                // every run gets its own contract account and so its own storage
+                let mut contract = [0xff; 32];
+                contract[..8].copy_from_slice(&seed.to_le_bytes());
+                ink::env::test::set_callee::<ink::env::DefaultEnvironment>(AccountId::from(contract));
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+                let mut mytoken = FungibleToken::new(
+                    "MyToken".to_string(),
+                    "MTK".to_string(),
+                    1000,
+                    false,
+                    None,
+                    0
+                );
+
+                let ops = random_ops(seed, 50, holders.len());
+                for (i, op) in ops.iter().enumerate() {
+                    match *op {
+                        Op::Transfer { from, to, value } => {
+                            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(holders[from]);
+                            let _ = mytoken.transfer(holders[to], value);
+                        }
+                        Op::MintTo { to, value } => {
+                            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+                            // minting past `Balance::MAX` must not corrupt state, skip it
+                            if mytoken.total_supply().checked_add(value).is_some() {
+                                mytoken.mint_to(holders[to], value);
+                            }
+                        }
+                        Op::Burn { from, value } => {
+                            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(holders[from]);
+                            let _ = mytoken.burn(value);
+                        }
+                    }
+
+                    // checking after every step makes the reported prefix the shortest failing one
+                    let sum = holders
+                        .iter()
+                        .try_fold(0 as Balance, |sum, holder| sum.checked_add(mytoken.balance_of(*holder)));
+                    assert_eq!(
+                        sum,
+                        Some(mytoken.total_supply()),
+                        "seed {} broke conservation after {} ops: {:?}",
+                        seed,
+                        i + 1,
+                        &ops[..=i]
+                    );
+                }
+            }
+        }
+
+        #[ink::test]
+        fn self_transfer_keeps_balance() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(mytoken.transfer(accounts.alice, 60), Ok(100));
+            assert_eq!(mytoken.balance_of(accounts.alice), 100);
+            assert_eq!(mytoken.burn(30), Ok(()));
+            assert_eq!(mytoken.total_supply(), 70);
+            assert_eq!(mytoken.burn(71), Err(Error::InsufficientBalance));
+        }
+
+        #[ink::test]
+        fn allowance_used_event_works() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(mytoken.approve(accounts.bob, 30, None), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(mytoken.transfer_from(accounts.alice, accounts.charlie, 20).is_ok());
+
+            // Approval first, then AllowanceUsed
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            let event = <Event as scale::Decode>::decode(&mut &events[1].data[..])
+                .expect("invalid event data");
+            match event {
+                Event::AllowanceUsed(AllowanceUsed { owner, spender, spent, remaining }) => {
+                    assert_eq!(owner, accounts.alice);
+                    assert_eq!(spender, accounts.bob);
+                    assert_eq!(spent, 20);
+                    assert_eq!(remaining, 10);
+                }
+                _ => panic!("expected AllowanceUsed"),
+            }
+        }
+
+        #[ink::test]
+        fn holders_track_non_zero_balances() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(mytoken.holders_paged(0, 10), vec![(accounts.alice, 100)]);
+
+            mytoken.transfer(accounts.bob, 40).unwrap();
+            mytoken.mint_to(accounts.charlie, 5);
+            assert_eq!(
+                mytoken.holders_paged(0, 10),
+                vec![(accounts.alice, 60), (accounts.bob, 40), (accounts.charlie, 5)]
+            );
+            assert_eq!(mytoken.holders_paged(1, 1), vec![(accounts.bob, 40)]);
+            assert_eq!(mytoken.holders_paged(3, 10), Vec::new());
+
+            // emptied accounts drop out of the list
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.transfer(accounts.alice, 40).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            mytoken.burn(5).unwrap();
+            assert_eq!(mytoken.holders_paged(0, 10), vec![(accounts.alice, 100)]);
+
+            // a zero-value transfer does not add the recipient
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            mytoken.transfer(accounts.eve, 0).unwrap();
+            assert_eq!(mytoken.holders_paged(0, 10), vec![(accounts.alice, 100)]);
+        }
+
+        #[ink::test]
+        fn allowance_expiry_works() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1000);
+            assert_eq!(mytoken.approve(accounts.bob, 30, Some(2000)), Ok(()));
+            assert_eq!(mytoken.allowance_expiry(accounts.alice, accounts.bob), Some(2000));
+
+            // still valid up to and including the expiry
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2000);
+            assert!(mytoken.transfer_from(accounts.alice, accounts.charlie, 10).is_ok());
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 20);
+            assert_eq!(mytoken.allowance_expiry(accounts.alice, accounts.bob), Some(2000));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2001);
+            assert_eq!(
+                mytoken.transfer_from(accounts.alice, accounts.charlie, 10),
+                Err(Error::AllowanceExpired)
+            );
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(mytoken.balance_of(accounts.charlie), 10);
+
+            // allowances without an expiry never lapse
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(mytoken.approve(accounts.bob, 30, None), Ok(()));
+            assert_eq!(mytoken.allowance_expiry(accounts.alice, accounts.bob), None);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(mytoken.transfer_from(accounts.alice, accounts.charlie, 10).is_ok());
+        }
+
+        #[ink::test]
+        fn initial_supply_cannot_be_credited_twice() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(mytoken.initialize(1000), Err(Error::AlreadyInitialized));
+            assert_eq!(mytoken.total_supply(), 100);
+            assert_eq!(mytoken.balance_of(accounts.alice), 100);
+            assert_eq!(mytoken.holders_paged(0, 10), vec![(accounts.alice, 100)]);
+        }
+
+        #[ink::test]
+        fn transfer_to_zero_address_is_rejected() {
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let zero = AccountId::from([0u8; 32]);
+
+            assert_eq!(mytoken.transfer(zero, 10), Err(Error::ZeroAddress));
+            assert_eq!(mytoken.transfer_from(accounts.alice, zero, 10), Err(Error::ZeroAddress));
+            assert_eq!(mytoken.balance_of(accounts.alice), 100);
+            assert_eq!(mytoken.balance_of(zero), 0);
+        }
+
+        #[ink::test]
+        fn claim_vested_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.vest(accounts.bob, 1000, 100, 100);
+            assert_eq!(mytoken.get_vesting(accounts.bob), Some((1000, 100, 100, 0)));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(mytoken.claim_vested(), Err(Error::NoVestingSchedule));
+
+            // 0% of the duration
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(mytoken.claim_vested(), Ok(0));
+            assert_eq!(mytoken.balance_of(accounts.bob), 0);
+
+            // 50%
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(150);
+            assert_eq!(mytoken.claim_vested(), Ok(500));
+            assert_eq!(mytoken.claim_vested(), Ok(0));
+            assert_eq!(mytoken.balance_of(accounts.bob), 500);
+            assert_eq!(mytoken.total_supply(), 600);
+
+            // 100%, and nothing more after the end
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(200);
+            assert_eq!(mytoken.claim_vested(), Ok(500));
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(300);
+            assert_eq!(mytoken.claim_vested(), Ok(0));
+            assert_eq!(mytoken.balance_of(accounts.bob), 1000);
+            assert_eq!(mytoken.total_supply(), 1100);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn vest_is_owner_only() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.vest(accounts.bob, 1000, 0, 100);
+        }
+
+        #[ink::test]
+        fn initial_allowance_is_set_at_deployment() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                Some((accounts.django, 40)),
+                0,
+            );
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.django), 40);
+            assert_eq!(mytoken.allowance_expiry(accounts.alice, accounts.django), None);
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn set_owner_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.set_owner(accounts.bob);
+            assert_eq!(mytoken.get_owner(), accounts.bob);
+
+            // only the new owner may mint now
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.mint_to(accounts.bob, 10);
+            assert_eq!(mytoken.balance_of(accounts.bob), 10);
+        }
+
+        #[ink::test]
+        fn new_with_balances_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mytoken = FungibleToken::new_with_balances(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                vec![(accounts.bob, 30), (accounts.charlie, 70)],
+            )
+            .unwrap();
+            assert_eq!(mytoken.total_supply(), 100);
+            assert_eq!(mytoken.balance_of(accounts.bob), 30);
+            assert_eq!(mytoken.balance_of(accounts.charlie), 70);
+            assert_eq!(mytoken.balance_of(accounts.alice), 0);
+            assert_eq!(mytoken.get_owner(), accounts.alice);
+            assert_eq!(
+                mytoken.holders_paged(0, 10),
+                vec![(accounts.bob, 30), (accounts.charlie, 70)]
+            );
+        }
+
+        #[ink::test]
+        fn new_with_balances_rejects_bad_sets() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let overflowing = FungibleToken::new_with_balances(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                vec![(accounts.bob, Balance::MAX), (accounts.charlie, 1)],
+            );
+            assert_eq!(overflowing.err(), Some(Error::Overflow));
+
+            let duplicated = FungibleToken::new_with_balances(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                vec![(accounts.bob, 1), (accounts.charlie, 1), (accounts.bob, 1)],
+            );
+            assert_eq!(duplicated.err(), Some(Error::DuplicateHolder));
+        }
+
+        #[ink::test]
+        fn try_balance_of_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert_eq!(mytoken.try_balance_of(accounts.alice), Some(100));
+            assert_eq!(mytoken.try_balance_of(accounts.bob), None);
+            assert_eq!(mytoken.balance_of(accounts.bob), 0);
+
+            // an emptied balance is still told apart from one that never existed
+            assert!(mytoken.transfer(accounts.bob, 10).is_ok());
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(mytoken.transfer(accounts.alice, 10).is_ok());
+            assert_eq!(mytoken.try_balance_of(accounts.bob), Some(0));
+            assert_eq!(mytoken.try_balance_of(accounts.charlie), None);
+        }
+
+        #[ink::test]
+        fn minted_tokens_are_locked_up() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                50
+            );
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            mytoken.mint_to(accounts.bob, 20);
+            assert_eq!(mytoken.locked_until(accounts.bob), Some(60));
+            // the initial supply is not minted through `mint_to`
+            assert_eq!(mytoken.locked_until(accounts.alice), None);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(mytoken.transfer(accounts.charlie, 5), Err(Error::LockedUp));
+            assert_eq!(mytoken.approve(accounts.charlie, 5, None), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                mytoken.transfer_from(accounts.bob, accounts.charlie, 5),
+                Err(Error::LockedUp)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(60);
+            assert_eq!(mytoken.transfer_from(accounts.bob, accounts.charlie, 5), Ok(15));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(mytoken.transfer(accounts.charlie, 5), Ok(10));
+            assert_eq!(mytoken.balance_of(accounts.charlie), 10);
+        }
+
+        #[ink::test]
+        fn lock_up_covers_only_minted_tokens() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                50
+            );
+            assert_eq!(mytoken.transfer(accounts.bob, 30), Ok(70));
+            mytoken.mint_to(accounts.bob, 20);
+            assert_eq!(mytoken.locked_balance(accounts.bob), 20);
+            // a zero mint locks nothing
+            mytoken.mint_to(accounts.alice, 0);
+            assert_eq!(mytoken.locked_until(accounts.alice), None);
+            assert_eq!(mytoken.transfer(accounts.charlie, 70), Ok(0));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(mytoken.transfer(accounts.charlie, 31), Err(Error::LockedUp));
+            assert_eq!(mytoken.transfer(accounts.charlie, 51), Err(Error::InsufficientBalance));
+            assert_eq!(mytoken.transfer(accounts.charlie, 30), Ok(20));
+            assert_eq!(mytoken.transfer(accounts.charlie, 1), Err(Error::LockedUp));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(50);
+            assert_eq!(mytoken.locked_balance(accounts.bob), 0);
+            assert_eq!(mytoken.transfer(accounts.charlie, 20), Ok(0));
+        }
+
+        #[ink::test]
+        fn revoke_allowance_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert_eq!(mytoken.approve(accounts.bob, 30, Some(1000)), Ok(()));
+            mytoken.revoke_allowance(accounts.bob);
+            assert_eq!(mytoken.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(mytoken.allowance_expiry(accounts.alice, accounts.bob), None);
+
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let event = <Event as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("invalid event data");
+            match event {
+                Event::Approval(Approval { owner, spender, value }) => {
+                    assert_eq!(owner, accounts.alice);
+                    assert_eq!(spender, accounts.bob);
+                    assert_eq!(value, 0);
+                }
+                _ => panic!("expected Approval"),
+            }
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                mytoken.transfer_from(accounts.alice, accounts.bob, 1),
+                Err(Error::InsufficientAllowance)
+            );
+        }
+
+        #[ink::test]
+        fn snapshot_holders_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert!(mytoken.transfer(accounts.bob, 30).is_ok());
+            assert!(mytoken.transfer(accounts.charlie, 20).is_ok());
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            // bob empties his balance and drops out of the snapshot
+            assert!(mytoken.transfer(accounts.django, 30).is_ok());
+
+            let mut snapshot = mytoken.snapshot_holders();
+            snapshot.sort();
+            let mut expected = vec![(accounts.alice, 50), (accounts.charlie, 20), (accounts.django, 30)];
+            expected.sort();
+            assert_eq!(snapshot, expected);
+            assert_eq!(
+                snapshot.iter().map(|(_, balance)| balance).sum::<Balance>(),
+                mytoken.total_supply()
+            );
+        }
+
+        #[ink::test]
+        fn pending_owner_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert_eq!(mytoken.pending_owner(), None);
+
+            mytoken.propose_owner(accounts.bob);
+            assert_eq!(mytoken.pending_owner(), Some(accounts.bob));
+            // proposing alone does not hand the token over
+            assert_eq!(mytoken.get_owner(), accounts.alice);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.accept_ownership();
+            assert_eq!(mytoken.get_owner(), accounts.bob);
+            assert_eq!(mytoken.pending_owner(), None);
+
+            // cancelling or handing the token over directly drops the proposal
+            mytoken.propose_owner(accounts.charlie);
+            mytoken.cancel_ownership_proposal();
+            assert_eq!(mytoken.pending_owner(), None);
+            mytoken.propose_owner(accounts.charlie);
+            mytoken.set_owner(accounts.alice);
+            assert_eq!(mytoken.pending_owner(), None);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn accept_ownership_requires_pending_owner() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.propose_owner(accounts.bob);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            mytoken.accept_ownership();
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn set_owner_clears_stale_proposal() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.propose_owner(accounts.bob);
+            mytoken.set_owner(accounts.charlie);
+
+            // bob's proposal went away with the old owner
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.accept_ownership();
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn cancelled_proposal_cannot_be_accepted() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.propose_owner(accounts.bob);
+            mytoken.cancel_ownership_proposal();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            mytoken.accept_ownership();
+        }
+
+        #[ink::test]
+        fn transfer_above_total_supply_is_invalid() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            assert_eq!(mytoken.transfer(accounts.bob, 101), Err(Error::InvalidAmount));
+            assert_eq!(mytoken.approve(accounts.bob, Balance::MAX, None), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                mytoken.transfer_from(accounts.alice, accounts.bob, 101),
+                Err(Error::InvalidAmount)
+            );
+            // the whole supply is still a valid amount
+            assert_eq!(mytoken.transfer_from(accounts.alice, accounts.bob, 100), Ok(0));
+            assert_eq!(mytoken.transfer(accounts.alice, 100), Ok(0));
+        }
+
+        #[ink::test]
+        fn claim_vested_rejects_overflowing_schedule() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut mytoken = FungibleToken::new(
+                "MyToken".to_string(),
+                "MTK".to_string(),
+                100,
+                false,
+                None,
+                0
+            );
+            mytoken.vest(accounts.bob, Balance::MAX, 0, 100);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(50);
+            assert_eq!(mytoken.claim_vested(), Err(Error::Overflow));
+            assert_eq!(mytoken.balance_of(accounts.bob), 0);
+        }
+    }
+
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+        use token_receiver::TokenReceiverRef;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        #[ink_e2e::test(additional_contracts = "../token_receiver/Cargo.toml")]
+        async fn transfer_notifies_receiver(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let token_ctor = FungibleTokenRef::new("MyToken".to_string(), "MTK".to_string(), 100, true, None, 0);
+            let token = client
+                .instantiate("fungibleToken", &ink_e2e::alice(), token_ctor, 0, None)
+                .await
+                .expect("token instantiate failed")
+                .account_id;
+
+            let receiver_ctor = TokenReceiverRef::new();
+            let receiver = client
+                .instantiate("token_receiver", &ink_e2e::alice(), receiver_ctor, 0, None)
+                .await
+                .expect("receiver instantiate failed")
+                .account_id;
+
+            let transfer = build_message::<FungibleTokenRef>(token.clone())
+                .call(|t| t.transfer(receiver.clone(), 10));
+            client
+                .call(&ink_e2e::alice(), transfer, 0, None)
+                .await
+                .expect("transfer failed");
+
+            let last_received = build_message::<TokenReceiverRef>(receiver.clone())
+                .call(|r| r.get_last_received());
+            let last_received = client
+                .call_dry_run(&ink_e2e::alice(), &last_received, 0, None)
+                .await
+                .return_value();
+            let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+            assert_eq!(last_received, Some((token, alice, 10)));
+
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,288 @@
+//! Off-chain stand-in for the `Erc20` token contracts called by the escrows.
+//!
+//! The ink! 4 off-chain environment cannot invoke other contracts, so under
+//! `cfg(test)` the escrows hand their call params to [`try_invoke`], which
+//! decodes the selector and arguments and applies them to an in-memory ledger
+//! keyed by token account. The selectors match the ones `FungibleToken` exposes.
+//! It also answers `price_of` for accounts acting as a `PriceOracle`, and
+//! `get_deposited`/`pull_deposit` for accounts acting as an `Escrow`, and `swap`
+//! for accounts acting as a `SwapRouter`.
+
+// shared by every contract crate, not each of them uses all the helpers
+#![allow(dead_code)]
+
+use ink::env::call::{Call, CallParams};
+use ink::env::DefaultEnvironment;
+use ink::primitives::AccountId;
+use scale::{Decode, Encode};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+type Balance = u128;
+
+pub const TRANSFER_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::transfer");
+pub const BALANCE_OF_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::balance_of");
+pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0, 0, 0, 6];
+pub const ALLOWANCE_SELECTOR: [u8; 4] = [0, 0, 0, 7];
+pub const GET_NAME_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::get_name");
+pub const GET_SYMBOL_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::get_symbol");
+pub const PRICE_OF_SELECTOR: [u8; 4] = ink::selector_bytes!("price_of");
+pub const GET_DEPOSITED_SELECTOR: [u8; 4] = ink::selector_bytes!("get_deposited");
+pub const PULL_DEPOSIT_SELECTOR: [u8; 4] = ink::selector_bytes!("pull_deposit");
+pub const SWAP_SELECTOR: [u8; 4] = ink::selector_bytes!("swap");
+
+#[derive(Default)]
+struct Ledger {
+    // (token, owner) -> balance
+    balances: HashMap<(AccountId, AccountId), Balance>,
+    // (token, owner, spender) -> allowance
+    allowances: HashMap<(AccountId, AccountId, AccountId), Balance>,
+    // tokens whose transfers always revert
+    rejecting: HashSet<AccountId>,
+    // (oracle, token) -> price
+    prices: HashMap<(AccountId, AccountId), Balance>,
+    // token -> amount burnt from every transfer, like a fee-on-transfer token
+    transfer_fees: HashMap<AccountId, Balance>,
+    // (escrow, token, depositor) -> amount deposited
+    deposits: HashMap<(AccountId, AccountId, AccountId), Balance>,
+    // token -> (name, symbol)
+    metadata: HashMap<AccountId, (String, String)>,
+    // (router, token in, token out) -> amount out per token in
+    swap_rates: HashMap<(AccountId, AccountId, AccountId), Balance>,
+}
+
+type Hook = Box<dyn FnMut(AccountId)>;
+
+thread_local! {
+    static LEDGER: RefCell<Ledger> = RefCell::new(Ledger::default());
+    static HOOK: RefCell<Option<Hook>> = RefCell::new(None);
+}
+
+/// Runs `hook` with the callee at the start of every call, before it is applied.
+///
+/// `Mapping` writes reach the off-chain storage right away, so a fresh instance
+/// built inside the hook sees what the contract stored before calling out.
+pub fn on_invoke(hook: impl FnMut(AccountId) + 'static) {
+    HOOK.with(|slot| *slot.borrow_mut() = Some(Box::new(hook)));
+}
+
+/// Credits `amount` of `token` to `owner`.
+pub fn mint(token: AccountId, owner: AccountId, amount: Balance) {
+    LEDGER.with(|ledger| {
+        let mut ledger = ledger.borrow_mut();
+        *ledger.balances.entry((token, owner)).or_default() += amount;
+    })
+}
+
+pub fn balance_of(token: AccountId, owner: AccountId) -> Balance {
+    LEDGER.with(|ledger| ledger.borrow().balance(token, owner))
+}
+
+pub fn approve(token: AccountId, owner: AccountId, spender: AccountId, amount: Balance) {
+    LEDGER.with(|ledger| {
+        ledger
+            .borrow_mut()
+            .allowances
+            .insert((token, owner, spender), amount);
+    })
+}
+
+pub fn allowance(token: AccountId, owner: AccountId, spender: AccountId) -> Balance {
+    LEDGER.with(|ledger| ledger.borrow().allowance(token, owner, spender))
+}
+
+/// Makes `oracle` quote `price` for `token`.
+pub fn set_price(oracle: AccountId, token: AccountId, price: Balance) {
+    LEDGER.with(|ledger| {
+        ledger.borrow_mut().prices.insert((oracle, token), price);
+    })
+}
+
+/// Records a deposit of `amount` of `token` by `depositor` into `escrow`,
+/// crediting the escrow with the tokens.
+pub fn deposit(escrow: AccountId, token: AccountId, depositor: AccountId, amount: Balance) {
+    LEDGER.with(|ledger| {
+        let mut ledger = ledger.borrow_mut();
+        *ledger.balances.entry((token, escrow)).or_default() += amount;
+        *ledger.deposits.entry((escrow, token, depositor)).or_default() += amount;
+    })
+}
+
+/// Makes `router` pay `rate` of `token_out` per `token_in` swapped, out of its own balance.
+pub fn set_swap_rate(router: AccountId, token_in: AccountId, token_out: AccountId, rate: Balance) {
+    LEDGER.with(|ledger| {
+        ledger
+            .borrow_mut()
+            .swap_rates
+            .insert((router, token_in, token_out), rate);
+    })
+}
+
+/// Sets the name and symbol `token` reports.
+pub fn set_metadata(token: AccountId, name: &str, symbol: &str) {
+    LEDGER.with(|ledger| {
+        ledger
+            .borrow_mut()
+            .metadata
+            .insert(token, (name.to_string(), symbol.to_string()));
+    })
+}
+
+/// Makes every transfer of `token` deliver `fee` less than the amount sent.
+pub fn set_transfer_fee(token: AccountId, fee: Balance) {
+    LEDGER.with(|ledger| {
+        ledger.borrow_mut().transfer_fees.insert(token, fee);
+    })
+}
+
+/// Makes every `transfer`/`transfer_from` on `token` revert from now on.
+pub fn reject_transfers(token: AccountId) {
+    LEDGER.with(|ledger| {
+        ledger.borrow_mut().rejecting.insert(token);
+    })
+}
+
+/// Executes the call described by `params` as if `caller` had invoked the token.
+///
+/// Returns `None` when the token would have reverted.
+pub fn try_invoke<Args, R>(
+    caller: AccountId,
+    params: &CallParams<DefaultEnvironment, Call<DefaultEnvironment>, Args, R>,
+) -> Option<R>
+where
+    Args: Encode,
+    R: Decode,
+{
+    let token = *params.callee();
+    // taken out while it runs so the hook may itself call into the mock
+    if let Some(mut hook) = HOOK.with(|slot| slot.borrow_mut().take()) {
+        hook(token);
+        HOOK.with(|slot| *slot.borrow_mut() = Some(hook));
+    }
+    let input = params.exec_input().encode();
+    let (selector, mut args) = input.split_at(4);
+    let output = LEDGER.with(|ledger| {
+        ledger
+            .borrow_mut()
+            .dispatch(token, caller, selector.try_into().ok()?, &mut args)
+    })?;
+    R::decode(&mut &output[..]).ok()
+}
+
+impl Ledger {
+    fn balance(&self, token: AccountId, owner: AccountId) -> Balance {
+        self.balances.get(&(token, owner)).copied().unwrap_or(0)
+    }
+
+    fn allowance(&self, token: AccountId, owner: AccountId, spender: AccountId) -> Balance {
+        self.allowances
+            .get(&(token, owner, spender))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn deposited(&self, escrow: AccountId, token: AccountId, depositor: AccountId) -> Balance {
+        self.deposits
+            .get(&(escrow, token, depositor))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn dispatch(
+        &mut self,
+        token: AccountId,
+        caller: AccountId,
+        selector: [u8; 4],
+        args: &mut &[u8],
+    ) -> Option<Vec<u8>> {
+        match selector {
+            BALANCE_OF_SELECTOR => {
+                let owner = AccountId::decode(args).ok()?;
+                Some(self.balance(token, owner).encode())
+            }
+            ALLOWANCE_SELECTOR => {
+                let owner = AccountId::decode(args).ok()?;
+                let spender = AccountId::decode(args).ok()?;
+                Some(self.allowance(token, owner, spender).encode())
+            }
+            TRANSFER_SELECTOR => {
+                let to = AccountId::decode(args).ok()?;
+                let value = Balance::decode(args).ok()?;
+                self.move_tokens(token, caller, to, value)
+            }
+            TRANSFER_FROM_SELECTOR => {
+                let from = AccountId::decode(args).ok()?;
+                let to = AccountId::decode(args).ok()?;
+                let value = Balance::decode(args).ok()?;
+                // like `FungibleToken`, moving your own tokens needs no allowance
+                let allowance = self.allowance(token, from, caller);
+                if caller != from && allowance < value {
+                    return None;
+                }
+                let output = self.move_tokens(token, from, to, value)?;
+                if caller != from {
+                    self.allowances
+                        .insert((token, from, caller), allowance - value);
+                }
+                Some(output)
+            }
+            GET_DEPOSITED_SELECTOR => {
+                let asset = AccountId::decode(args).ok()?;
+                let depositor = AccountId::decode(args).ok()?;
+                Some(self.deposited(token, asset, depositor).encode())
+            }
+            PULL_DEPOSIT_SELECTOR => {
+                let asset = AccountId::decode(args).ok()?;
+                let from = AccountId::decode(args).ok()?;
+                let value = Balance::decode(args).ok()?;
+                // here `token` is the escrow and `caller` its operator
+                let deposited = self.deposited(token, asset, from);
+                if deposited < value {
+                    return None;
+                }
+                self.move_tokens(asset, token, caller, value)?;
+                self.deposits.insert((token, asset, from), deposited - value);
+                Some(Ok::<(), ()>(()).encode())
+            }
+            SWAP_SELECTOR => {
+                // here `token` is the router, which already holds the input
+                let token_in = AccountId::decode(args).ok()?;
+                let amount_in = Balance::decode(args).ok()?;
+                let token_out = AccountId::decode(args).ok()?;
+                let to = AccountId::decode(args).ok()?;
+                let rate = *self.swap_rates.get(&(token, token_in, token_out))?;
+                let amount_out = amount_in.checked_mul(rate)?;
+                self.move_tokens(token_out, token, to, amount_out)?;
+                Some(Ok::<Balance, ()>(amount_out).encode())
+            }
+            GET_NAME_SELECTOR => Some(self.metadata.get(&token)?.0.encode()),
+            GET_SYMBOL_SELECTOR => Some(self.metadata.get(&token)?.1.encode()),
+            PRICE_OF_SELECTOR => {
+                let asset = AccountId::decode(args).ok()?;
+                let price = self.prices.get(&(token, asset)).copied().unwrap_or(0);
+                Some(price.encode())
+            }
+            _ => None,
+        }
+    }
+
+    fn move_tokens(
+        &mut self,
+        token: AccountId,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    ) -> Option<Vec<u8>> {
+        if self.rejecting.contains(&token) {
+            return None;
+        }
+        let from_balance = self.balance(token, from);
+        if from_balance < value {
+            return None;
+        }
+        let fee = self.transfer_fees.get(&token).copied().unwrap_or(0).min(value);
+        self.balances.insert((token, from), from_balance - value);
+        *self.balances.entry((token, to)).or_default() += value - fee;
+        Some(Ok::<Balance, ()>(self.balance(token, from)).encode())
+    }
+}
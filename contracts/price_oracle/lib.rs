@@ -0,0 +1,67 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::price_oracle::{PriceOracle, PriceOracleRef};
+
+#[ink::contract]
+mod price_oracle {
+    use ink::storage::Mapping;
+
+    #[ink(storage)]
+    pub struct PriceOracle {
+        // mapping from token to its price in the reference currency
+        prices: Mapping<AccountId, Balance>,
+        owner: AccountId,
+    }
+
+    impl PriceOracle {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                prices: Mapping::new(),
+                owner: Self::env().caller(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_price(&mut self, token: AccountId, price: Balance) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.prices.insert(token, &price);
+        }
+
+        #[ink(message)]
+        pub fn price_of(&self, token: AccountId) -> Balance {
+            self.prices.get(token).unwrap_or(0)
+        }
+    }
+
+    impl Default for PriceOracle {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn set_price_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut oracle = PriceOracle::new();
+            assert_eq!(oracle.price_of(accounts.eve), 0);
+
+            oracle.set_price(accounts.eve, 42);
+            assert_eq!(oracle.price_of(accounts.eve), 42);
+        }
+
+        #[ink::test]
+        #[should_panic]
+        fn set_price_is_owner_only() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut oracle = PriceOracle::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            oracle.set_price(accounts.eve, 42);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::swap_router::{Error, SwapRouter, SwapRouterRef};
+
+/// A fixed-rate router for tests: callers send the input tokens first, then
+/// `swap` pays out the output token from the router's own liquidity.
+#[ink::contract]
+mod swap_router {
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::DefaultEnvironment;
+    use ink::storage::Mapping;
+
+    const TRANSFER_SELECTOR: [u8; 4] = ink::selector_bytes!("Erc20::transfer");
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        NoRate,
+        TransferFailed,
+    }
+
+    #[ink(storage)]
+    pub struct SwapRouter {
+        // (token in, token out) -> amount out per token in
+        rates: Mapping<(AccountId, AccountId), Balance>,
+        owner: AccountId,
+    }
+
+    impl SwapRouter {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                rates: Mapping::new(),
+                owner: Self::env().caller(),
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_rate(&mut self, token_in: AccountId, token_out: AccountId, rate: Balance) {
+            let caller = self.env().caller();
+            assert_eq!(caller, self.owner);
+            self.rates.insert((token_in, token_out), &rate);
+        }
+
+        #[ink(message)]
+        pub fn quote(&self, token_in: AccountId, amount_in: Balance, token_out: AccountId) -> Balance {
+            let rate = self.rates.get((token_in, token_out)).unwrap_or(0);
+            amount_in.saturating_mul(rate)
+        }
+
+        /// Pays `to` the output for `amount_in`, which the caller already sent.
+        ///
+        /// The stub does not check the input arrived, it only prices it.
+        #[ink(message)]
+        pub fn swap(
+            &mut self,
+            token_in: AccountId,
+            amount_in: Balance,
+            token_out: AccountId,
+            to: AccountId,
+        ) -> Result<Balance, Error> {
+            if !self.rates.contains((token_in, token_out)) {
+                return Err(Error::NoRate);
+            }
+            let amount_out = self.quote(token_in, amount_in, token_out);
+            build_call::<DefaultEnvironment>()
+                .call(token_out)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                        .push_arg(to)
+                        .push_arg(amount_out),
+                )
+                // only success matters, the token's error is not decoded
+                .returns::<Result<Balance, u8>>()
+                .try_invoke()
+                .ok()
+                .and_then(|result| result.ok())
+                .and_then(|result| result.ok())
+                .ok_or(Error::TransferFailed)?;
+            Ok(amount_out)
+        }
+    }
+
+    impl Default for SwapRouter {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn quote_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut router = SwapRouter::new();
+            assert_eq!(router.quote(accounts.eve, 10, accounts.frank), 0);
+
+            router.set_rate(accounts.eve, accounts.frank, 3);
+            assert_eq!(router.quote(accounts.eve, 10, accounts.frank), 30);
+            // rates are directional
+            assert_eq!(router.quote(accounts.frank, 10, accounts.eve), 0);
+            assert_eq!(
+                router.swap(accounts.frank, 10, accounts.eve, accounts.alice),
+                Err(Error::NoRate)
+            );
+        }
+    }
+}
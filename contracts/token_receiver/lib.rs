@@ -0,0 +1,62 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::token_receiver::{TokenReceiver, TokenReceiverRef};
+
+#[ink::contract]
+mod token_receiver {
+
+    #[ink(storage)]
+    #[derive(Default)]
+    pub struct TokenReceiver {
+        // number of transfer notifications received
+        received_count: u32,
+        // (token, from, value) of the latest notification
+        last_received: Option<(AccountId, AccountId, Balance)>,
+    }
+
+    impl TokenReceiver {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        #[ink(message)]
+        pub fn on_token_received(&mut self, from: AccountId, value: Balance) {
+            // the caller is the token contract notifying the transfer
+            let token = self.env().caller();
+            self.received_count += 1;
+            self.last_received = Some((token, from, value));
+        }
+
+        #[ink(message)]
+        pub fn get_received_count(&self) -> u32 {
+            self.received_count
+        }
+
+        #[ink(message)]
+        pub fn get_last_received(&self) -> Option<(AccountId, AccountId, Balance)> {
+            self.last_received
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn on_token_received_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut receiver = TokenReceiver::new();
+            assert_eq!(receiver.get_received_count(), 0);
+            assert_eq!(receiver.get_last_received(), None);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            receiver.on_token_received(accounts.alice, 10);
+            assert_eq!(receiver.get_received_count(), 1);
+            assert_eq!(
+                receiver.get_last_received(),
+                Some((accounts.eve, accounts.alice, 10))
+            );
+        }
+    }
+}